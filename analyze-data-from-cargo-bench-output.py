@@ -16,7 +16,7 @@
 # along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 import fileinput
-import functools
+import json
 import sys
 from pathlib import Path
 
@@ -26,49 +26,23 @@ HERE = Path(__file__).parent.resolve()
 
 
 def parse(lines):
-    "Parse the lines of the `cargo bench` output"
-    for line in lines:
-        if "... bench:" not in line:
-            continue
-
-        _test, path, _elipsis, _bench, mean, _nsiter, _pm, stddev = line.split()
-        *_, implementation, benchmark = path.split("::")
-
-        # There is a stray ")" in the output
-        stddev = stddev.rstrip(")")
-        if benchmark.startswith("bench_"):
-            test_case = benchmark.removeprefix("bench_")
-        else:
-            test_case = benchmark
-
-        yield implementation, test_case, to_int(mean), to_int(stddev)
-
-
-def to_int(measurement: str) -> int:
-    return int(measurement.replace(",", ""))
-
-
-def add_test_case_size(df):
-    return df.assign(bytes_per_iteration=df["test_case"].apply(test_case_size))
-
-
-@functools.cache
-def test_case_size(test_case_name):
     """
-    Return the size of the test case, in bytes.
+    Parse the JSON Lines from `cargo bench -- --format json` and
+    `python/benchmark-python.py --format json`. Each record has the size of the test
+    case, so we don't need to go looking for it.
     """
-    # Find the test case. It will be somewhere in  ./target/releasee
-    filename = test_case_name.replace("_", "-") + ".bin"
-    builds_dir = HERE / "target" / "release" / "build"
-    assert builds_dir.is_dir()
-    matches = list(builds_dir.glob(f"**/{filename}"))
-    assert (
-        len(matches) == 1
-    ), f"could not find exactly one test case called '{filename}'; found: {matches}"
-    (test_case_path,) = matches
+    for line in lines:
+        if not line.startswith("{"):
+            continue
 
-    # Get its size:
-    return test_case_path.stat().st_size
+        record = json.loads(line)
+        yield (
+            record["implementation"],
+            record["dataset"],
+            round(record["ns_per_iter"]),
+            round(record["deviation_ns"]),
+            record["bytes"],
+        )
 
 
 def add_bytes_per_second(df):
@@ -98,6 +72,8 @@ def add_language_and_name(df):
             return "Rust", implementation_name
         elif first_part.startswith("np"):
             return "Python", implementation_name
+        elif first_part.startswith("parallel"):
+            return "Rust", implementation_name
         raise ValueError(implementation_name)
 
     result = df["implementation"].apply(extract).apply(pd.Series)
@@ -120,7 +96,10 @@ def add_category(df):
 
 
 def nullify_test_case_size_conditionally(df):
-    weird_cases = ("vec_eq_do_nothing_but_allocate", "vec_eq_only_prefix")
+    weird_cases = (
+        "vec_eq_do_nothing_but_allocate_sound",
+        "vec_eq_only_prefix",
+    )
     is_weird_case = df["implementation"].apply(lambda name: name not in weird_cases)
     return df.assign(
         bytes_per_iteration=df["bytes_per_iteration"].where(is_weird_case, pd.NA)
@@ -144,10 +123,18 @@ def add_minimum_time(df):
 rows = parse(fileinput.input(encoding="UTF-8"))
 
 benchmarks = (
-    pd.DataFrame(rows, columns=["implementation", "test_case", "mean_ns", "stddev_ns"])
+    pd.DataFrame(
+        rows,
+        columns=[
+            "implementation",
+            "test_case",
+            "mean_ns",
+            "stddev_ns",
+            "bytes_per_iteration",
+        ],
+    )
     .pipe(add_language_and_name)
     .pipe(add_category)
-    .pipe(add_test_case_size)
     .pipe(nullify_test_case_size_conditionally)
     .pipe(add_bytes_per_second)
     .pipe(add_gigabytes_per_second)
@@ -0,0 +1,297 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The benchmarks. Run them with `cargo bench`, or `cargo bench -- FILTER` to run
+//! only the benchmarks whose names contain FILTER.
+
+mod harness;
+
+use std::ffi::CString;
+use std::hint::black_box;
+
+use fast_sp::data::generate::{self, Dataset};
+use fast_sp::parallel::{count_parallel, default_threads};
+use fast_sp::rng::Rng;
+use fast_sp::*;
+use harness::{cache, Bencher, Measurement};
+
+const DATASETS: [(&str, &str); 2] = [
+    ("random_sp", data::RANDOM_SP),
+    ("random_printable", data::RANDOM_PRINTABLE),
+];
+
+fn main() {
+    let mut b = Bencher::from_args();
+
+    // The same size and seed as the other datasets.
+    let manifest = data::random_sp_manifest();
+    let generated = generate::standard(manifest.size as usize, manifest.seed);
+
+    bench_implementations(&mut b, &generated);
+    bench_branch_sweep(&mut b, manifest.seed);
+    bench_size_sweep(&mut b, manifest.seed);
+    bench_tallies(&mut b);
+    bench_weighted(&mut b);
+    bench_vec_eq(&mut b);
+    bench_parallel(&mut b);
+    bench_nonzero(&mut b);
+
+    b.finish();
+}
+
+/// Benchmarks `f` on every dataset.
+fn bench_datasets<R>(b: &mut Bencher, name: &str, mut f: impl FnMut(&[u8]) -> R) {
+    for (dataset, contents) in DATASETS {
+        let bytes = contents.as_bytes();
+        b.bench(name, dataset, bytes.len(), || f(black_box(bytes)));
+    }
+}
+
+/// Benchmarks every implementation on every dataset, including the generated ones.
+fn bench_implementations(b: &mut Bencher, generated: &[Dataset]) {
+    let datasets: Vec<(&str, &[u8])> = DATASETS
+        .iter()
+        .map(|&(name, contents)| (name, contents.as_bytes()))
+        .chain(
+            generated
+                .iter()
+                .map(|d| (d.name.as_str(), d.bytes.as_slice())),
+        )
+        .collect();
+
+    for counter in supported() {
+        for &(dataset, bytes) in &datasets {
+            bench_counter(b, counter, dataset, bytes);
+        }
+    }
+}
+
+/// Benchmarks `counter` on `bytes`. Implementations that need a NUL-terminated
+/// string skip datasets that contain NUL.
+fn bench_counter(
+    b: &mut Bencher,
+    counter: &dyn Counter,
+    dataset: &str,
+    bytes: &[u8],
+) -> Option<Measurement> {
+    match counter.kernel() {
+        Kernel::Sized(count) => b.bench(counter.name(), dataset, bytes.len(), || {
+            count(black_box(bytes))
+        }),
+        Kernel::NulTerminated(count) => {
+            let buffer = CString::new(bytes).ok()?;
+            let sentence = buffer.as_c_str();
+            b.bench(counter.name(), dataset, bytes.len(), || {
+                count(black_box(sentence))
+            })
+        }
+    }
+}
+
+/// Benchmarks every implementation on inputs that are more and more predictable.
+/// Branchless implementations should be just as fast on all of them; branchy ones
+/// shouldn't.
+fn bench_branch_sweep(b: &mut Bencher, seed: u64) {
+    // Small enough to stay in cache, so that memory bandwidth doesn't hide anything.
+    const LEN: usize = 1024 * 1024;
+
+    for (title, sweep) in [
+        ("p('s')", generate::probability_sweep(LEN, seed)),
+        ("period", generate::period_sweep(LEN, seed)),
+    ] {
+        let points: Vec<_> = sweep
+            .iter()
+            .map(|d| SweepPoint {
+                heading: d.name.rsplit('_').next().unwrap().to_owned(),
+                dataset: d.name.clone(),
+                bytes: &d.bytes,
+            })
+            .collect();
+        bench_sweep(b, title, &points);
+    }
+}
+
+/// Benchmarks every implementation on inputs from 1 KiB (which fits in any L1
+/// cache) to 1 GiB (which doesn't fit in any cache at all), and on inputs exactly
+/// the size of each of this CPU's data caches, to find where each implementation
+/// falls off a cache cliff.
+fn bench_size_sweep(b: &mut Bencher, seed: u64) {
+    // When just checking that the benchmarks work, don't bother with big inputs.
+    let max_size = if b.is_timing() { 1 << 30 } else { 1 << 20 };
+
+    // Powers of 4:
+    let mut sizes: Vec<_> = (0..)
+        .map(|i| 1024 << (2 * i))
+        .take_while(|&size| size <= max_size)
+        .map(|size| (human_size(size), size))
+        .collect();
+    for cache in cache::data_caches() {
+        if cache.size <= max_size {
+            sizes.retain(|&(_, size)| size != cache.size);
+            sizes.push((
+                format!("{}_{}", human_size(cache.size), cache.name),
+                cache.size,
+            ));
+        }
+    }
+    sizes.sort_by_key(|&(_, size)| size);
+
+    let wanted = supported().any(|counter| {
+        sizes
+            .iter()
+            .any(|(label, _)| b.wants(counter.name(), &format!("size_{label}")))
+    });
+    if !wanted {
+        // Don't generate a GiB of text for nothing.
+        return;
+    }
+
+    let text = generate::english_like(&mut Rng::new(seed), sizes.last().unwrap().1);
+    let points: Vec<_> = sizes
+        .iter()
+        .map(|(label, size)| SweepPoint {
+            heading: label.replace('_', " "),
+            dataset: format!("size_{label}"),
+            bytes: &text[..*size],
+        })
+        .collect();
+    bench_sweep(b, "size", &points);
+}
+
+/// One input in a sweep.
+struct SweepPoint<'a> {
+    /// The column heading in the table.
+    heading: String,
+    dataset: String,
+    bytes: &'a [u8],
+}
+
+/// Benchmarks every implementation on every point, and prints a table of each
+/// implementation's throughput (in GiB/s), with a column for each point.
+fn bench_sweep(b: &mut Bencher, title: &str, points: &[SweepPoint]) {
+    let mut rows = Vec::new();
+    for counter in supported() {
+        let throughputs: Vec<_> = points
+            .iter()
+            .map(|point| {
+                bench_counter(b, counter, &point.dataset, point.bytes)
+                    .map(|m| m.gib_per_s(point.bytes.len()))
+            })
+            .collect();
+        if throughputs.iter().all(Option::is_none) {
+            continue;
+        }
+
+        let cells = throughputs
+            .iter()
+            .map(|throughput| match throughput {
+                Some(throughput) => format!("{throughput:.2}"),
+                None => "-".to_owned(),
+            })
+            .collect();
+        rows.push((counter.name().to_owned(), cells));
+    }
+
+    let headings: Vec<_> = points.iter().map(|point| point.heading.clone()).collect();
+    b.print_table(&format!("{title} (GiB/s)"), &headings, &rows);
+}
+
+/// E.g., "48KiB", or "2MiB".
+fn human_size(bytes: usize) -> String {
+    for (shift, unit) in [(30, "GiB"), (20, "MiB"), (10, "KiB")] {
+        if bytes >= 1 << shift && bytes.trailing_zeros() >= shift {
+            return format!("{}{unit}", bytes >> shift);
+        }
+    }
+    format!("{bytes}B")
+}
+
+fn bench_tallies(b: &mut Bencher) {
+    bench_datasets(b, "rust_emulate_numpy_tally", rust_emulate_numpy_tally);
+    bench_datasets(b, "rust_portable_simd_tally", rust_portable_simd_tally);
+    bench_datasets(b, "c_state_machine_tally", c_state_machine_tally);
+    bench_datasets(b, "c_count_machine_tally", c_count_machine_tally);
+}
+
+fn bench_weighted(b: &mut Bencher) {
+    let table = &WeightTable::SP;
+    bench_datasets(b, "rust_weighted_scalar", |bytes| {
+        rust_weighted_scalar(bytes, table)
+    });
+    bench_datasets(b, "rust_weighted_lookup", |bytes| {
+        rust_weighted_lookup(bytes, table)
+    });
+    bench_datasets(b, "rust_weighted_portable_simd", |bytes| {
+        rust_weighted_portable_simd(bytes, table)
+    });
+}
+
+fn bench_vec_eq(b: &mut Bencher) {
+    bench_datasets(b, "vec_eq", |bytes| vec_eq(bytes, b's'));
+    bench_datasets(b, "vec_eq_only_prefix", |bytes| {
+        vec_eq_only_prefix(bytes, b's')
+    });
+    bench_datasets(b, "vec_eq_simd_sound", |bytes| {
+        vec_eq_simd_sound(bytes, b's')
+    });
+    bench_datasets(b, "vec_eq_do_nothing_but_allocate_sound", |bytes| {
+        vec_eq_do_nothing_but_allocate_sound(bytes, b's')
+    });
+
+    const N: usize = 32;
+    let input = data::RANDOM_SP.as_bytes();
+    let n_initial_bytes = input.len() % N;
+    let bytes = input.len() - n_initial_bytes;
+
+    let mut buffer = vec![false; input.len()];
+    b.bench("vec_eq_only_simd", "random_sp", bytes, || {
+        vec_eq_only_simd(
+            black_box(&input[n_initial_bytes..]),
+            &mut buffer[n_initial_bytes..],
+            b's',
+        )
+    });
+
+    let mut buffer = Vec::<bool>::with_capacity(input.len());
+    let buffer = &mut buffer.spare_capacity_mut()[..input.len()];
+    b.bench("vec_eq_only_simd_sound", "random_sp", bytes, || {
+        vec_eq_only_simd_sound(
+            black_box(&input[n_initial_bytes..]),
+            &mut buffer[n_initial_bytes..],
+            b's',
+        )
+    });
+}
+
+fn bench_parallel(b: &mut Bencher) {
+    for (name, threads) in [
+        ("threads_1", 1),
+        ("threads_2", 2),
+        ("threads_4", 4),
+        ("threads_8", 8),
+        ("threads_available", default_threads()),
+    ] {
+        bench_datasets(b, &format!("parallel::{name}"), |bytes| {
+            count_parallel(bytes, threads)
+        });
+    }
+}
+
+fn bench_nonzero(b: &mut Bencher) {
+    for (dataset, contents) in DATASETS {
+        let vec = vec_eq(contents.as_bytes(), b's');
+        b.bench("nonzero", dataset, vec.len(), || nonzeros(black_box(&vec)));
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Saving benchmark results as a named baseline, and comparing new results with
+//! one.
+//!
+//! A baseline is saved in `target/baselines/NAME.tsv`, with a line for each
+//! benchmark: its name, then every sample (in nanoseconds per iteration), separated
+//! by tabs. Keeping every sample, and not just the mean, lets us tell a real change
+//! from noise with [Welch's t-test][welch].
+//!
+//! [welch]: https://en.wikipedia.org/wiki/Welch%27s_t-test
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// A change is only significant if it's this unlikely to be noise.
+pub const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// The samples of each benchmark, by name (e.g., "rust_iter::random_sp").
+#[derive(Debug, Default)]
+pub struct Baseline {
+    pub samples: BTreeMap<String, Vec<f64>>,
+}
+
+impl Baseline {
+    /// Loads the baseline called `name`.
+    pub fn load(name: &str) -> io::Result<Baseline> {
+        let contents = fs::read_to_string(path(name))?;
+
+        let mut samples = BTreeMap::new();
+        for (n, line) in contents.lines().enumerate() {
+            let mut fields = line.split('\t');
+            let benchmark = fields.next().unwrap_or_default();
+            let values: Result<Vec<f64>, _> = fields.map(str::parse).collect();
+            match values {
+                Ok(values) if !benchmark.is_empty() && !values.is_empty() => {
+                    samples.insert(benchmark.to_owned(), values);
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}:{}: malformed line", path(name).display(), n + 1),
+                    ))
+                }
+            }
+        }
+
+        Ok(Baseline { samples })
+    }
+
+    /// Saves this as the baseline called `name`, replacing any that's already there.
+    pub fn save(&self, name: &str) -> io::Result<()> {
+        let mut contents = String::new();
+        for (benchmark, samples) in &self.samples {
+            contents.push_str(benchmark);
+            for sample in samples {
+                write!(contents, "\t{sample}").unwrap();
+            }
+            contents.push('\n');
+        }
+
+        let path = path(name);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, contents)
+    }
+}
+
+/// Where the baseline called `name` is saved.
+pub fn path(name: &str) -> PathBuf {
+    let target = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target"));
+    target.join("baselines").join(format!("{name}.tsv"))
+}
+
+/// How a benchmark changed since the baseline.
+#[derive(Debug, Clone, Copy)]
+pub struct Comparison {
+    /// The change in mean time per iteration, in percent. Positive is slower.
+    pub change: f64,
+    /// The probability of seeing a difference at least this big if nothing really
+    /// changed, according to Welch's t-test.
+    pub p_value: f64,
+}
+
+impl Comparison {
+    /// Compares the `new` samples with the `old` ones.
+    pub fn new(old: &[f64], new: &[f64]) -> Comparison {
+        let (old_mean, new_mean) = (mean(old), mean(new));
+        Comparison {
+            change: (new_mean - old_mean) / old_mean * 100.0,
+            p_value: welch_t_test(old, new),
+        }
+    }
+
+    pub fn is_significant(&self) -> bool {
+        self.p_value < SIGNIFICANCE_LEVEL
+    }
+
+    /// Whether this is a significant slowdown of more than `threshold` percent.
+    pub fn is_regression(&self, threshold: f64) -> bool {
+        self.is_significant() && self.change > threshold
+    }
+}
+
+fn mean(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+fn variance(samples: &[f64]) -> f64 {
+    let mean = mean(samples);
+    let sum_of_squares: f64 = samples.iter().map(|x| (x - mean).powi(2)).sum();
+    sum_of_squares / (samples.len() - 1) as f64
+}
+
+/// The two-sided p-value of Welch's t-test, which, unlike Student's, doesn't assume
+/// that both sets of samples are equally noisy.
+fn welch_t_test(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() < 2 || b.len() < 2 {
+        // We can't tell how noisy either of them is.
+        return 1.0;
+    }
+
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let (v_a, v_b) = (variance(a) / n_a, variance(b) / n_b);
+    let difference = mean(b) - mean(a);
+    if v_a + v_b == 0.0 {
+        // No noise at all, so any difference is real.
+        return if difference == 0.0 { 1.0 } else { 0.0 };
+    }
+
+    let t = difference / (v_a + v_b).sqrt();
+    // The Welch–Satterthwaite equation:
+    let degrees_of_freedom =
+        (v_a + v_b).powi(2) / (v_a.powi(2) / (n_a - 1.0) + v_b.powi(2) / (n_b - 1.0));
+
+    // The two-sided tail of Student's t-distribution:
+    let x = degrees_of_freedom / (degrees_of_freedom + t * t);
+    incomplete_beta(x, degrees_of_freedom / 2.0, 0.5)
+}
+
+/// The regularized incomplete beta function, I_x(a, b), from its continued
+/// fraction, which converges quickly when x < (a + 1) / (a + b + 2). Otherwise, we
+/// use the symmetry I_x(a, b) = 1 - I_{1-x}(b, a).
+fn incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    if x > (a + 1.0) / (a + b + 2.0) {
+        return 1.0 - incomplete_beta(1.0 - x, b, a);
+    }
+
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp() / a;
+
+    // Lentz's algorithm.
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 / nonzero(1.0 - (a + b) * x / (a + 1.0), TINY);
+    let mut fraction = d;
+    for m in 1..300 {
+        let m = m as f64;
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        for numerator in [even, odd] {
+            d = 1.0 / nonzero(1.0 + numerator * d, TINY);
+            c = nonzero(1.0 + numerator / c, TINY);
+            fraction *= c * d;
+        }
+        if (c * d - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+
+    front * fraction
+}
+
+fn nonzero(x: f64, tiny: f64) -> f64 {
+    if x.abs() < tiny {
+        tiny
+    } else {
+        x
+    }
+}
+
+/// The natural log of the gamma function, with the Lanczos approximation (g = 7).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        // The reflection formula.
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+
+    let x = x - 1.0;
+    let mut sum = COEFFICIENTS[0];
+    for (i, coefficient) in COEFFICIENTS.iter().enumerate().skip(1) {
+        sum += coefficient / (x + i as f64);
+    }
+    let t = x + 7.5;
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
@@ -0,0 +1,69 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Finding out how big this CPU's caches are.
+
+use std::fs;
+use std::path::Path;
+
+/// A data (or unified) cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    /// E.g., "L1d" or "L2".
+    pub name: String,
+    pub size: usize,
+}
+
+/// The data caches of the first CPU, from smallest to largest, according to
+/// `/sys/devices/system/cpu/cpu0/cache`. Empty if that doesn't exist (i.e., when
+/// not on Linux).
+pub fn data_caches() -> Vec<Cache> {
+    let Ok(entries) = fs::read_dir("/sys/devices/system/cpu/cpu0/cache") else {
+        return Vec::new();
+    };
+
+    let mut caches: Vec<_> = entries
+        .filter_map(|entry| read_cache(&entry.ok()?.path()))
+        .collect();
+    caches.sort_by_key(|cache| cache.size);
+    caches
+}
+
+fn read_cache(index: &Path) -> Option<Cache> {
+    let read = |name| fs::read_to_string(index.join(name)).ok();
+
+    let level = read("level")?;
+    let suffix = match read("type")?.trim() {
+        "Data" => "d",
+        "Unified" => "",
+        _ => return None,
+    };
+
+    Some(Cache {
+        name: format!("L{}{suffix}", level.trim()),
+        size: parse_size(read("size")?.trim())?,
+    })
+}
+
+/// Parses sizes like "48K" or "32M".
+fn parse_size(size: &str) -> Option<usize> {
+    let (digits, multiplier) = match size.as_bytes().last()? {
+        b'K' => (&size[..size.len() - 1], 1 << 10),
+        b'M' => (&size[..size.len() - 1], 1 << 20),
+        b'G' => (&size[..size.len() - 1], 1 << 30),
+        _ => (size, 1),
+    };
+    Some(digits.parse::<usize>().ok()? * multiplier)
+}
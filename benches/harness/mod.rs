@@ -0,0 +1,430 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A tiny benchmark harness that works on stable Rust.
+//!
+//! It times benchmarks the same way as `timeit` in `python/benchmark-python.py`:
+//! after warming up, it finds how many iterations take at least [MIN_SAMPLE_TIME]
+//! (the way `Timer.autorange()` does), then times [SAMPLES] batches of that many
+//! iterations. By default, results are printed in the same format as `cargo bench`,
+//! followed by the median and the throughput. Use `--format json` (which
+//! `analyze-data-from-cargo-bench-output.py` reads) or `--format csv` for
+//! machine-readable results.
+//!
+//! Like libtest, it only times benchmarks when it's given `--bench` (which
+//! `cargo bench` does). Otherwise (e.g., `cargo test --benches`), every benchmark
+//! is run once, to check that it works. Any other argument filters the benchmarks
+//! by name, like `cargo bench -- FILTER`.
+//!
+//! `--save-baseline NAME` saves the results as a [baseline], and `--baseline NAME`
+//! compares the results with one. Then, if any benchmark got significantly slower by
+//! more than `--threshold PERCENT` (5% by default), the benchmarks exit with an
+//! error.
+
+mod baseline;
+pub mod cache;
+mod output;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use baseline::{Baseline, Comparison};
+use output::{Format, Record};
+
+/// How long to run a benchmark before timing it.
+const WARM_UP_TIME: Duration = Duration::from_millis(100);
+/// The least time that one sample should take.
+const MIN_SAMPLE_TIME: Duration = Duration::from_millis(200);
+/// How many samples to take.
+const SAMPLES: usize = 10;
+/// How much slower (in percent) a benchmark can get before it's a regression.
+const DEFAULT_THRESHOLD: f64 = 5.0;
+
+/// Runs benchmarks, and prints their results.
+pub struct Bencher {
+    filters: Vec<String>,
+    time: bool,
+    format: Format,
+    printed_header: bool,
+    /// The name to save this run's results as.
+    save_baseline: Option<String>,
+    /// The baseline to compare with, and its name.
+    baseline: Option<(String, Baseline)>,
+    threshold: f64,
+    results: Baseline,
+    /// The benchmarks that got slower than the threshold, and by how much.
+    regressions: Vec<(String, Comparison)>,
+}
+
+impl Bencher {
+    /// Configures the harness from the command-line arguments.
+    pub fn from_args() -> Bencher {
+        let mut filters = Vec::new();
+        let mut time = false;
+        let mut format = Format::Text;
+        let mut save_baseline = None;
+        let mut baseline_name = None;
+        let mut threshold = DEFAULT_THRESHOLD;
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if let Some(name) = option_value("--format", &arg, &mut args) {
+                format = Format::parse(&name).unwrap_or_else(|| {
+                    fail(&format!(
+                        "unknown --format {name:?}: expected text, json, or csv"
+                    ))
+                });
+                continue;
+            }
+            if let Some(name) = option_value("--save-baseline", &arg, &mut args) {
+                save_baseline = Some(name);
+                continue;
+            }
+            if let Some(name) = option_value("--baseline", &arg, &mut args) {
+                baseline_name = Some(name);
+                continue;
+            }
+            if let Some(percent) = option_value("--threshold", &arg, &mut args) {
+                threshold = percent.parse().unwrap_or_else(|_| {
+                    fail(&format!("--threshold {percent:?} is not a percentage"))
+                });
+                continue;
+            }
+
+            match arg.as_str() {
+                "--bench" => time = true,
+                // Ignore libtest's options, like --nocapture.
+                _ if arg.starts_with('-') => {}
+                _ => filters.push(arg),
+            }
+        }
+
+        // Baselines are only meaningful when timing.
+        let baseline = baseline_name.filter(|_| time).map(|name| {
+            let baseline = Baseline::load(&name).unwrap_or_else(|error| {
+                fail(&format!(
+                    "cannot load baseline {name:?} from {}: {error}",
+                    baseline::path(&name).display()
+                ))
+            });
+            (name, baseline)
+        });
+
+        Bencher {
+            filters,
+            time,
+            format,
+            printed_header: false,
+            save_baseline: save_baseline.filter(|_| time),
+            baseline,
+            threshold,
+            results: Baseline::default(),
+            regressions: Vec::new(),
+        }
+    }
+
+    /// Whether `implementation` on `dataset` would be benchmarked, or is filtered out.
+    /// Use this to avoid preparing expensive inputs for nothing.
+    pub fn wants(&self, implementation: &str, dataset: &str) -> bool {
+        let name = format!("{implementation}::{dataset}");
+        self.filters.is_empty() || self.filters.iter().any(|filter| name.contains(filter))
+    }
+
+    /// Whether benchmarks are timed (`cargo bench`), or just run once to check that
+    /// they work (`cargo test --benches`).
+    pub fn is_timing(&self) -> bool {
+        self.time
+    }
+
+    /// Benchmarks `f`, which processes `bytes` bytes of `dataset` with
+    /// `implementation`, unless it's filtered out. Returns the measurement, if `f`
+    /// was timed.
+    pub fn bench<R>(
+        &mut self,
+        implementation: &str,
+        dataset: &str,
+        bytes: usize,
+        mut f: impl FnMut() -> R,
+    ) -> Option<Measurement> {
+        if !self.wants(implementation, dataset) {
+            return None;
+        }
+
+        let name = format!("{implementation}::{dataset}");
+
+        if !self.time {
+            black_box(f());
+            println!("test {name} ... ok");
+            return None;
+        }
+
+        let measurement = Measurement::take(&mut f);
+        let comparison = self
+            .baseline
+            .as_ref()
+            .and_then(|(_, baseline)| baseline.samples.get(&name))
+            .map(|old_samples| Comparison::new(old_samples, &measurement.samples));
+        let record = Record {
+            implementation,
+            dataset,
+            bytes,
+            measurement: &measurement,
+            compared: self.baseline.is_some(),
+            comparison,
+        };
+
+        if !self.printed_header {
+            if let Some(header) = self.format.header(self.baseline.is_some()) {
+                println!("{header}");
+            }
+            self.printed_header = true;
+        }
+        println!("{}", record.format(self.format));
+
+        if let Some(comparison) = comparison {
+            let verdict = if comparison.is_regression(self.threshold) {
+                self.regressions.push((name.clone(), comparison));
+                "regressed"
+            } else if !comparison.is_significant() {
+                "no change"
+            } else if comparison.change < 0.0 {
+                "improved"
+            } else {
+                "slower, within threshold"
+            };
+            if self.format == Format::Text {
+                println!(
+                    "    change: {:+.2}% (p = {:.3}): {verdict}",
+                    comparison.change, comparison.p_value
+                );
+            }
+        }
+
+        self.results
+            .samples
+            .insert(name, measurement.samples.clone());
+        Some(measurement)
+    }
+
+    /// Saves the baseline, if asked to, and exits with an error if anything
+    /// regressed since the baseline we compared with. Call this after every
+    /// benchmark.
+    pub fn finish(self) {
+        if let Some(name) = &self.save_baseline {
+            // Keep the results of benchmarks that were filtered out this time.
+            let mut saved = Baseline::load(name).unwrap_or_default();
+            saved.samples.extend(self.results.samples);
+            if let Err(error) = saved.save(name) {
+                fail(&format!("cannot save baseline {name:?}: {error}"));
+            }
+            eprintln!(
+                "saved baseline {name:?} to {}",
+                baseline::path(name).display()
+            );
+        }
+
+        let Some((name, _)) = &self.baseline else {
+            return;
+        };
+        if self.regressions.is_empty() {
+            eprintln!("no regressions since baseline {name:?}");
+            return;
+        }
+
+        eprintln!(
+            "regressed by more than {}% since baseline {name:?}:",
+            self.threshold
+        );
+        for (benchmark, comparison) in &self.regressions {
+            eprintln!(
+                "    {benchmark}: {:+.2}% (p = {:.3})",
+                comparison.change, comparison.p_value
+            );
+        }
+        std::process::exit(1);
+    }
+
+    /// Prints a table, with a row for each name, and a column for each heading. Only
+    /// in text format, since it would get in the way of JSON or CSV.
+    pub fn print_table(&self, title: &str, headings: &[String], rows: &[(String, Vec<String>)]) {
+        if self.format != Format::Text || rows.is_empty() {
+            return;
+        }
+
+        let name_width = rows
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain([title.len()])
+            .max()
+            .unwrap_or(0);
+        let width = |column: usize| {
+            rows.iter()
+                .filter_map(|(_, cells)| cells.get(column))
+                .chain([&headings[column]])
+                .map(String::len)
+                .max()
+                .unwrap_or(0)
+        };
+
+        println!();
+        print!("{title:<name_width$}");
+        for (column, heading) in headings.iter().enumerate() {
+            print!("  {heading:>width$}", width = width(column));
+        }
+        println!();
+        for (name, cells) in rows {
+            print!("{name:<name_width$}");
+            for (column, cell) in cells.iter().enumerate() {
+                print!("  {cell:>width$}", width = width(column));
+            }
+            println!();
+        }
+        println!();
+    }
+}
+
+/// How long one iteration of a benchmark took, in nanoseconds, in each sample.
+#[derive(Debug)]
+pub struct Measurement {
+    /// How many times the benchmark ran per sample.
+    pub iterations: u64,
+    pub samples: Vec<f64>,
+    /// How many cycles one iteration took, on average, if we can count cycles.
+    pub cycles: Option<f64>,
+}
+
+impl Measurement {
+    /// Warms up, autoranges, and then samples `f`.
+    pub fn take<R>(f: &mut impl FnMut() -> R) -> Measurement {
+        let start = Instant::now();
+        while start.elapsed() < WARM_UP_TIME {
+            black_box(f());
+        }
+
+        let iterations = autorange(f);
+        let mut samples = Vec::with_capacity(SAMPLES);
+        let mut total_cycles = Some(0);
+        for _ in 0..SAMPLES {
+            let start_cycles = read_cycle_counter();
+            let elapsed = time(f, iterations);
+            let end_cycles = read_cycle_counter();
+
+            samples.push(elapsed.as_nanos() as f64 / iterations as f64);
+            total_cycles = total_cycles
+                .zip(end_cycles.zip(start_cycles))
+                .map(|(total, (end, start))| total + end.wrapping_sub(start));
+        }
+
+        let n_iterations = (iterations * SAMPLES as u64) as f64;
+        Measurement {
+            iterations,
+            samples,
+            cycles: total_cycles.map(|cycles| cycles as f64 / n_iterations),
+        }
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.samples.iter().sum::<f64>() / self.samples.len() as f64
+    }
+
+    pub fn median(&self) -> f64 {
+        let mut sorted = self.samples.clone();
+        sorted.sort_by(f64::total_cmp);
+
+        // With an odd number of samples, these are both the middle sample.
+        let n = sorted.len();
+        (sorted[(n - 1) / 2] + sorted[n / 2]) / 2.0
+    }
+
+    /// Throughput in GiB/s, when each iteration processes `bytes` bytes, based on the
+    /// mean time per iteration.
+    pub fn gib_per_s(&self, bytes: usize) -> f64 {
+        bytes as f64 / self.mean() * 1e9 / (1u64 << 30) as f64
+    }
+
+    /// Cycles per byte, when each iteration processes `bytes` bytes, if we can count
+    /// cycles.
+    pub fn cycles_per_byte(&self, bytes: usize) -> Option<f64> {
+        self.cycles.map(|cycles| cycles / bytes as f64)
+    }
+
+    /// The sample standard deviation.
+    pub fn stddev(&self) -> f64 {
+        if self.samples.len() < 2 {
+            return 0.0;
+        }
+
+        let mean = self.mean();
+        let sum_of_squares: f64 = self.samples.iter().map(|x| (x - mean).powi(2)).sum();
+        (sum_of_squares / (self.samples.len() - 1) as f64).sqrt()
+    }
+}
+
+/// If `arg` is `option`, returns the next argument; if it's `option=value`, returns
+/// the value.
+fn option_value(
+    option: &str,
+    arg: &str,
+    args: &mut impl Iterator<Item = String>,
+) -> Option<String> {
+    match arg.strip_prefix(option) {
+        Some("") => args.next(),
+        Some(value) => value.strip_prefix('=').map(str::to_owned),
+        None => None,
+    }
+}
+
+/// Reports a usage error, and exits.
+fn fail(message: &str) -> ! {
+    eprintln!("{message}");
+    std::process::exit(2);
+}
+
+/// Returns how many iterations of `f` take at least [MIN_SAMPLE_TIME], trying 1, 2,
+/// 5, 10, 20, 50, ... iterations, like Python's `Timer.autorange()`.
+fn autorange<R>(f: &mut impl FnMut() -> R) -> u64 {
+    let mut i = 1;
+    loop {
+        for multiplier in [1, 2, 5] {
+            let iterations = i * multiplier;
+            if time(f, iterations) >= MIN_SAMPLE_TIME {
+                return iterations;
+            }
+        }
+        i *= 10;
+    }
+}
+
+fn time<R>(f: &mut impl FnMut() -> R, iterations: u64) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(f());
+    }
+    start.elapsed()
+}
+
+/// Reads the time-stamp counter, on x86-64. It ticks at a constant rate (usually
+/// the CPU's base frequency), whatever the actual clock speed, so with turbo boost,
+/// it undercounts how many cycles the core really ran for.
+#[cfg(target_arch = "x86_64")]
+fn read_cycle_counter() -> Option<u64> {
+    // Safety: every x86-64 CPU has rdtsc.
+    Some(unsafe { std::arch::x86_64::_rdtsc() })
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn read_cycle_counter() -> Option<u64> {
+    None
+}
@@ -0,0 +1,195 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Printing results as text (like `cargo bench`), JSON Lines, or CSV.
+
+use std::fmt::Write;
+
+use super::{Comparison, Measurement};
+
+/// How to print results. Choose with `--format text|json|csv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Like `cargo bench`, followed by the median, throughput, and cycles per byte.
+    Text,
+    /// One JSON object per line.
+    Json,
+    /// Comma-separated values, with a header.
+    Csv,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match name {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    /// What to print before the first record, if anything. When `compared` with a
+    /// baseline, records have two more fields.
+    pub fn header(self, compared: bool) -> Option<String> {
+        match self {
+            Format::Csv => {
+                let mut header = String::from(
+                    "implementation,dataset,bytes,iterations,samples,\
+                     ns_per_iter,median_ns_per_iter,deviation_ns,gib_per_s,cycles_per_byte",
+                );
+                if compared {
+                    header.push_str(",change_percent,p_value");
+                }
+                Some(header)
+            }
+            Format::Text | Format::Json => None,
+        }
+    }
+}
+
+/// The result of one benchmark.
+pub struct Record<'a> {
+    pub implementation: &'a str,
+    pub dataset: &'a str,
+    /// How many bytes one iteration processes.
+    pub bytes: usize,
+    pub measurement: &'a Measurement,
+    /// Whether we're comparing with a baseline at all.
+    pub compared: bool,
+    /// How it changed since the baseline, if the baseline has this benchmark.
+    pub comparison: Option<Comparison>,
+}
+
+impl Record<'_> {
+    pub fn format(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.to_text(),
+            Format::Json => self.to_json(),
+            Format::Csv => self.to_csv(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let m = self.measurement;
+        let mut text = format!(
+            "test {}::{} ... bench: {:>15} ns/iter (+/- {}) median {} ns/iter, {:.2} GiB/s",
+            self.implementation,
+            self.dataset,
+            thousands(m.mean()),
+            thousands(m.stddev()),
+            thousands(m.median()),
+            m.gib_per_s(self.bytes),
+        );
+        if let Some(cycles_per_byte) = m.cycles_per_byte(self.bytes) {
+            write!(text, ", {cycles_per_byte:.3} cycles/byte").unwrap();
+        }
+        text
+    }
+
+    fn to_json(&self) -> String {
+        let m = self.measurement;
+        let mut json = format!(
+            "{{\"implementation\":{},\"dataset\":{},\"bytes\":{},\"iterations\":{},\
+             \"samples\":{},\"ns_per_iter\":{:.1},\"median_ns_per_iter\":{:.1},\
+             \"deviation_ns\":{:.1},\"gib_per_s\":{:.4},\"cycles_per_byte\":{}",
+            json_string(self.implementation),
+            json_string(self.dataset),
+            self.bytes,
+            m.iterations,
+            m.samples.len(),
+            m.mean(),
+            m.median(),
+            m.stddev(),
+            m.gib_per_s(self.bytes),
+            m.cycles_per_byte(self.bytes)
+                .map_or("null".to_owned(), |cycles| format!("{cycles:.4}")),
+        );
+        if self.compared {
+            let (change, p_value) = self.comparison_fields("null");
+            write!(json, ",\"change_percent\":{change},\"p_value\":{p_value}").unwrap();
+        }
+        json.push('}');
+        json
+    }
+
+    fn to_csv(&self) -> String {
+        let m = self.measurement;
+        let mut csv = format!(
+            "{},{},{},{},{},{:.1},{:.1},{:.1},{:.4},{}",
+            csv_field(self.implementation),
+            csv_field(self.dataset),
+            self.bytes,
+            m.iterations,
+            m.samples.len(),
+            m.mean(),
+            m.median(),
+            m.stddev(),
+            m.gib_per_s(self.bytes),
+            m.cycles_per_byte(self.bytes)
+                .map_or(String::new(), |cycles| format!("{cycles:.4}")),
+        );
+        if self.compared {
+            let (change, p_value) = self.comparison_fields("");
+            write!(csv, ",{change},{p_value}").unwrap();
+        }
+        csv
+    }
+
+    /// The change in percent, and the p-value, or `missing` for both if the
+    /// baseline doesn't have this benchmark.
+    fn comparison_fields(&self, missing: &str) -> (String, String) {
+        match self.comparison {
+            Some(comparison) => (
+                format!("{:.2}", comparison.change),
+                format!("{:.4}", comparison.p_value),
+            ),
+            None => (missing.to_owned(), missing.to_owned()),
+        }
+    }
+}
+
+/// Formats a number of nanoseconds like `cargo bench` does: rounded, with commas.
+fn thousands(nanos: f64) -> String {
+    let digits = (nanos.round() as u64).to_string();
+    let groups: Vec<_> = digits
+        .as_bytes()
+        .rchunks(3)
+        .rev()
+        .map(|group| std::str::from_utf8(group).unwrap())
+        .collect();
+    groups.join(",")
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if c.is_control() => write!(result, "\\u{:04x}", c as u32).unwrap(),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
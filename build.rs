@@ -14,7 +14,31 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::env;
-use std::process::Command;
+use std::fs;
+use std::path::Path;
+
+// The same generator that the tests use. It's small enough to share by including
+// its source, rather than making a separate crate for it.
+#[allow(dead_code)]
+mod rng {
+    include!("src/rng.rs");
+}
+
+// Likewise, the manifests that the tests read back.
+#[allow(dead_code)]
+mod manifest {
+    include!("src/data/manifest.rs");
+}
+
+use manifest::{fnv1a64, Manifest};
+use rng::Rng;
+
+/// The size of each dataset: 12 MiB, the size of the L2 cache on the M1's
+/// performance cores.
+const DATASET_SIZE: usize = 12 * 1024 * 1024;
+/// The seed for the datasets, unless FAST_SP_SEED is set. generate-test-data.py
+/// uses the same default.
+const DEFAULT_SEED: u64 = 2023;
 
 fn main() {
     compile_c_library();
@@ -42,14 +66,55 @@ fn compile_c_library() {
     for source in sources {
         println!("cargo:rerun-if-changed={source}");
     }
+    println!("cargo:rerun-if-changed=c/tally.h");
 }
 
+/// Writes the datasets, and their manifests, into OUT_DIR. They're always the same
+/// for the same seed, byte for byte. `python3 generate-test-data.py` makes identical
+/// files, to check.
 fn generate_test_data() {
-    let script_name = "./generate-test-data.py";
     let out_dir = env::var("OUT_DIR").unwrap();
-    Command::new("python3")
-        .args([script_name, "-C", &out_dir])
-        .status()
-        .unwrap();
-    println!("cargo:rerun-if-changed={script_name}");
+    let out_dir = Path::new(&out_dir);
+
+    let seed = match env::var("FAST_SP_SEED") {
+        Ok(seed) => seed
+            .parse()
+            .unwrap_or_else(|_| panic!("FAST_SP_SEED should be a u64, not {seed:?}")),
+        Err(_) => DEFAULT_SEED,
+    };
+
+    // Both datasets come from the same stream, so they're not correlated.
+    let mut rng = Rng::new(seed);
+    let printable: Vec<u8> = (0..DATASET_SIZE)
+        .map(|_| b' ' + rng.below(u64::from(b'~' - b' ' + 1)) as u8)
+        .collect();
+    let sp: Vec<u8> = (0..DATASET_SIZE).map(|_| *rng.choose(b"ps")).collect();
+
+    write_dataset(out_dir, "random-printable", seed, &printable);
+    write_dataset(out_dir, "random-sp", seed, &sp);
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/rng.rs");
+    println!("cargo:rerun-if-changed=src/data/manifest.rs");
+    println!("cargo:rerun-if-env-changed=FAST_SP_SEED");
+}
+
+fn write_dataset(out_dir: &Path, name: &str, seed: u64, data: &[u8]) {
+    let s = data.iter().filter(|&&b| b == b's').count() as u64;
+    let p = data.iter().filter(|&&b| b == b'p').count() as u64;
+    let manifest = Manifest {
+        seed,
+        size: data.len() as u64,
+        checksum: fnv1a64(data),
+        s,
+        p,
+        balance: s as i64 - p as i64,
+    };
+
+    fs::write(out_dir.join(format!("{name}.bin")), data).unwrap();
+    fs::write(
+        out_dir.join(format!("{name}.manifest")),
+        manifest.to_string(),
+    )
+    .unwrap();
 }
@@ -1,6 +1,8 @@
 #include <stdio.h>
 #include <stdint.h>
 
+#include "tally.h"
+
 enum {
     COUNT_OTHER = 0,
     COUNT_S = 1,
@@ -13,14 +15,29 @@ static uint8_t categorize[256] = {
     COUNT_OTHER
 };
 
-int c_count_machine(char *input, size_t n) {
-    unsigned int counts[3] = { 0 };
-
+static inline void count_machine(char *input, size_t n, uint64_t counts[3]) {
     for (size_t i = 0; i < n; i++) {
         uint8_t c = input[i];
         counts[categorize[c]]++;
     }
+}
+
+int64_t c_count_machine(char *input, size_t n) {
+    uint64_t counts[3] = { 0 };
+    count_machine(input, n, counts);
+
+    int64_t result = (int64_t) counts[COUNT_S] - (int64_t) counts[COUNT_P];
+    return result;
+}
+
+struct tally c_count_machine_tally(char *input, size_t n) {
+    uint64_t counts[3] = { 0 };
+    count_machine(input, n, counts);
 
-    int result = counts[COUNT_S] - counts[COUNT_P];
+    struct tally result = {
+        .s = counts[COUNT_S],
+        .p = counts[COUNT_P],
+        .other = counts[COUNT_OTHER],
+    };
     return result;
 }
@@ -1,18 +1,18 @@
-#include <assert.h>
 #include <stdbool.h>
 #include <stddef.h>
 #include <stdint.h>
 
-static_assert(sizeof(int) == 4, "Cannot assume that C's int is i32 in Rust");
-
 /**
  * Owen Sheppard's original implementation.
  * From: https://owen.cafe/posts/six-times-faster-than-c/
  *
  * See assembly on Compiler Explorer: https://godbolt.org/z/4cqoqdbTq
+ *
+ * The blog post counts in an int, which overflows (undefined behaviour!) on
+ * inputs longer than INT_MAX, so this counts in 64 bits instead.
  */
-int run_switches(char *input) {
-  int res = 0;
+int64_t run_switches(char *input) {
+  int64_t res = 0;
   while (true) {
     char c = *input++;
     switch (c) {
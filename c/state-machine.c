@@ -1,6 +1,8 @@
 #include <stdio.h>
 #include <stdint.h>
 
+#include "tally.h"
+
 enum {
     COUNT_OTHER = 0,
     COUNT_S = 1,
@@ -19,16 +21,31 @@ static uint8_t col[256] = {
     0
 };
 
-int c_state_machine(char *input, size_t n) {
-    unsigned int counts[3] = { 0 };
-
+static inline void state_machine(char *input, size_t n, uint64_t counts[3]) {
     int state = COUNT_OTHER;
     for (size_t i = 0; i < n; i++) {
         uint8_t c = input[i];
         state = states[state][col[c]];
         counts[state]++;
     }
+}
+
+int64_t c_state_machine(char *input, size_t n) {
+    uint64_t counts[3] = { 0 };
+    state_machine(input, n, counts);
+
+    int64_t result = (int64_t) counts[COUNT_S] - (int64_t) counts[COUNT_P];
+    return result;
+}
+
+struct tally c_state_machine_tally(char *input, size_t n) {
+    uint64_t counts[3] = { 0 };
+    state_machine(input, n, counts);
 
-    int result = counts[COUNT_S] - counts[COUNT_P];
+    struct tally result = {
+        .s = counts[COUNT_S],
+        .p = counts[COUNT_P],
+        .other = counts[COUNT_OTHER],
+    };
     return result;
 }
@@ -0,0 +1,31 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#ifndef TALLY_H
+#define TALLY_H
+
+#include <stdint.h>
+
+/**
+ * How many of each kind of byte were in the input.
+ * Must have the same layout as Tally in src/implementations/tally.rs.
+ */
+struct tally {
+    uint64_t s;
+    uint64_t p;
+    uint64_t other;
+};
+
+#endif /* TALLY_H */
@@ -19,8 +19,8 @@
 /**
  * See assembly in Compiler Explorer: <https://godbolt.org/z/aPGWTW8WP>
  */
-int with_explicit_size(const char *input, size_t n) {
-    int res = 0;
+int64_t with_explicit_size(const char *input, size_t n) {
+    int64_t res = 0;
     for (size_t i = 0; i < n; i++) {
         char c = input[i];
         switch (c) {
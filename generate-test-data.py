@@ -14,13 +14,20 @@
 # along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 """
-generate-test-data.py - creates random test data, quickly
+generate-test-data.py - creates the same test data as build.rs
 
 SYNOPSIS
     python3 generate-test-data.py [-C directory]
 
 OPTIONS
     -C directory      if specifed, changes into this directory before executing
+
+build.rs generates the test data on its own. This script reimplements the same
+generator (SplitMix64, and Lemire's multiply-and-shift for ranges) with NumPy, as
+an independent cross-check that the data is what we think it is:
+
+    python3 generate-test-data.py -C /tmp
+    cmp /tmp/random-sp.bin target/release/build/fast-sp-*/out/random-sp.bin
 """
 
 import os
@@ -32,32 +39,56 @@ KILOBYTE = 1024
 MEGABYTE = 1024 * KILOBYTE
 SIZE = 12 * MEGABYTE
 
-generator = np.random.default_rng()
+# Must be the same as build.rs: DEFAULT_SEED, or FAST_SP_SEED if it's set.
+SEED = int(os.environ.get("FAST_SP_SEED", 2023))
 
+GAMMA = np.uint64(0x9E3779B97F4A7C15)
+
+
+def splitmix64(seed: int, start: int, n: int) -> np.ndarray:
+    """
+    The outputs of SplitMix64 (src/rng.rs), from the start-th to the (start + n)-th.
+    Each output only depends on its index, so they can all be computed at once.
+    """
+    with np.errstate(over="ignore"):
+        z = np.arange(start + 1, start + n + 1, dtype=np.uint64) * GAMMA
+        z += np.uint64(seed)
+        z = (z ^ (z >> np.uint64(30))) * np.uint64(0xBF58476D1CE4E5B9)
+        z = (z ^ (z >> np.uint64(27))) * np.uint64(0x94D049BB133111EB)
+        return z ^ (z >> np.uint64(31))
 
-def random_sp():
-    "Generate an array of random ASCII 's' or 'p' characters"
-    s_or_p = np.array([ord("p"), ord("s")], dtype=np.uint8)
-    return generator.choice(s_or_p, size=(SIZE,), shuffle=False)
 
+def below(x: np.ndarray, n: int) -> np.ndarray:
+    "Rng::below: (x * n) >> 64, without 128-bit integers. n must be less than 2**32."
+    n = np.uint64(n)
+    high = (x >> np.uint64(32)) * n
+    low = (x & np.uint64(0xFFFFFFFF)) * n
+    return (high + (low >> np.uint64(32))) >> np.uint64(32)
 
-def random_ascii_printable():
+
+def random_ascii_printable(start: int) -> np.ndarray:
     "Generate an array of random printable ASCII characters"
     min_char = ord(" ")  # Lowest, printable ASCII char
     max_char = ord("~")  # Highest, printable ASCII char
-    return generator.integers(
-        low=min_char, high=max_char, endpoint=True, dtype=np.uint8, size=(SIZE,)
-    )
+    offsets = below(splitmix64(SEED, start, SIZE), max_char - min_char + 1)
+    return (offsets + np.uint64(min_char)).astype(np.uint8)
+
+
+def random_sp(start: int) -> np.ndarray:
+    "Generate an array of random ASCII 's' or 'p' characters"
+    s_or_p = np.array([ord("p"), ord("s")], dtype=np.uint8)
+    return s_or_p[below(splitmix64(SEED, start, SIZE), 2)]
 
 
 if __name__ == "__main__":
     # Change directory, if specified:
-    if flag_index := sys.argv.index("-C"):
-        directory = sys.argv[flag_index + 1]
+    if "-C" in sys.argv:
+        directory = sys.argv[sys.argv.index("-C") + 1]
         os.chdir(directory)
 
+    # Both come from the same stream, in this order, just like in build.rs.
     with open("random-printable.bin", "wb") as data_file:
-        random_ascii_printable().tofile(data_file)
+        random_ascii_printable(start=0).tofile(data_file)
 
     with open("random-sp.bin", "wb") as data_file:
-        random_sp().tofile(data_file)
+        random_sp(start=SIZE).tofile(data_file)
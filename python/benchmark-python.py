@@ -13,6 +13,9 @@
 # You should have received a copy of the GNU Affero General Public License
 # along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+import importlib.util
+import json
+import sys
 import timeit
 from dataclasses import dataclass
 from itertools import product
@@ -24,6 +27,8 @@ from for_loop import python_for_loop
 from numpy_solution import python_numpy
 
 HERE = Path(__file__).parent.resolve()
+# Print JSON Lines instead of text with `--format json`, like `cargo bench`.
+JSON = "--format=json" in sys.argv or sys.argv[1:3] == ["--format", "json"]
 
 
 @dataclass
@@ -49,55 +54,89 @@ class Measurement:
 def benchmark() -> None:
     """
     Time all Python implementations, and prints the results in a way that vaguely
-    emulates cargo bench's output.
+    emulates cargo bench's output, or as JSON Lines with --format json.
     """
 
-    random_printable = load_test_data("random-printable.bin")
-    random_sp = load_test_data("random-sp.bin")
+    datasets = load_test_data()
 
     fns = ["python_for_loop", "python_numpy"]
-    test_cases = ["random_printable", "random_sp"]
+    test_cases = list(datasets)
     for fn, test_case in product(fns, test_cases):
-        namespace = globals() | {test_case: locals()[test_case]}
+        namespace = globals() | {test_case: datasets[test_case]}
         measurement = time(f"{fn}({test_case})", globals=namespace)
-        print_measurement(fn, test_case, measurement)
+        print_measurement(fn, test_case, measurement, len(datasets[test_case]))
 
     # Profile for just numpy returning the indices with a given value:
     for test_case in test_cases:
-        namespace = globals() | {test_case: locals()[test_case]}
+        namespace = globals() | {test_case: datasets[test_case]}
         measurement = time(f"{test_case} == b's'", globals=namespace)
-        print_measurement("np.count_nonzero", test_case, measurement)
+        print_measurement(
+            "np.count_nonzero", test_case, measurement, len(datasets[test_case])
+        )
 
     # Profile for just numpy's count_nonzero
     for test_case in test_cases:
-        namespace = globals() | {test_case: locals()[test_case]}
+        namespace = globals() | {test_case: datasets[test_case]}
         measurement = time(
             f"np.count_nonzero(array)",
             setup=f"array = {test_case} == b's'",
             globals=namespace,
         )
-        print_measurement("np.count_nonzero", test_case, measurement)
+        print_measurement(
+            "np.count_nonzero", test_case, measurement, len(datasets[test_case])
+        )
 
 
-def load_test_data(name: str):
+def load_test_data():
     """
-    Loads the test data used by `cargo bench`.
-
-    The test data will have been placed in target/release/build/<SOMETHING>/out.
-    <SOMETHING> is determined by cargo, so it's easier just to try to glob for
-    something matching the expected filename.
+    Generates the same test data as `cargo bench` uses (random_printable and
+    random_sp), with generate-test-data.py, so we don't have to find build.rs's
+    output directory.
     """
-    builds_dir = HERE.parent / "target" / "release" / "build"
-    assert builds_dir.is_dir()
-    (path,) = builds_dir.glob(f"**/{name}")
-    return np.fromfile(path, dtype=np.uint8)
+    spec = importlib.util.spec_from_file_location(
+        "generate_test_data", HERE.parent / "generate-test-data.py"
+    )
+    generate_test_data = importlib.util.module_from_spec(spec)
+    spec.loader.exec_module(generate_test_data)
+
+    # Both come from the same stream, in this order, just like in build.rs.
+    size = generate_test_data.SIZE
+    return {
+        "random_printable": generate_test_data.random_ascii_printable(start=0),
+        "random_sp": generate_test_data.random_sp(start=size),
+    }
 
 
-def print_measurement(fn: str, test_case: str, measurement: Measurement):
-    "Print measurements in a manner similar to cargo bench"
-    mean_time = int(as_nanos(measurement.mean_time))
-    stddev = int(as_nanos(measurement.fake_stddev))
-    print(f"test {fn}::{test_case} ... bench: {mean_time:,} ns/iter (+/- {stddev:,})")
+def print_measurement(fn: str, test_case: str, measurement: Measurement, size: int):
+    """
+    Print measurements in a manner similar to cargo bench, or as JSON Lines (in the
+    same schema as `cargo bench -- --format json`) with --format json.
+    """
+    mean_time = as_nanos(measurement.mean_time)
+    stddev = as_nanos(measurement.fake_stddev)
+    median_time = as_nanos(np.median(measurement.samples_secs))
+    gib_per_s = size / measurement.mean_time / 1024**3
+
+    if JSON:
+        record = {
+            "implementation": fn,
+            "dataset": test_case,
+            "bytes": size,
+            "iterations": measurement.iterations,
+            "samples": len(measurement.samples_secs),
+            "ns_per_iter": round(mean_time, 1),
+            "median_ns_per_iter": round(median_time, 1),
+            "deviation_ns": round(stddev, 1),
+            "gib_per_s": round(gib_per_s, 4),
+            "cycles_per_byte": None,
+        }
+        print(json.dumps(record, separators=(",", ":")))
+    else:
+        print(
+            f"test {fn}::{test_case} ... bench: {int(mean_time):,} ns/iter "
+            f"(+/- {int(stddev):,}) median {int(median_time):,} ns/iter, "
+            f"{gib_per_s:.2f} GiB/s"
+        )
 
 
 def time(stmt: str, **kwargs) -> Measurement:
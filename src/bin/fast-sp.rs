@@ -0,0 +1,286 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! fast-sp - count the number of 's' minus the number of 'p' in files
+//!
+//! SYNOPSIS
+//!     fast-sp [-i IMPLEMENTATION] [-t] [FILE ...]
+//!     fast-sp --list
+//!
+//! With no FILE, or when FILE is -, reads standard input.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use fast_sp::file::{self, Error};
+use fast_sp::stream;
+use fast_sp::{find, Counter, Tally, IMPLEMENTATIONS};
+
+const USAGE: &str = "\
+usage: fast-sp [-i IMPLEMENTATION] [-t] [FILE ...]
+       fast-sp --list
+
+Prints the number of 's' minus the number of 'p' in each FILE, and the total.
+With no FILE, or when FILE is -, reads standard input. With --tally, the columns
+are: balance, number of 's', number of 'p', number of other bytes, and FILE.
+
+options:
+  -i, --implementation NAME  count using NAME (default: rust_portable_simd)
+  -t, --tally                also print the number of 's', 'p' and other bytes
+                             (always counts using rust_portable_simd_tally)
+  -l, --list                 list the implementations, and exit
+  -h, --help                 print this message, and exit";
+
+const DEFAULT_IMPLEMENTATION: &str = "rust_portable_simd";
+
+struct Options {
+    counter: &'static dyn Counter,
+    tally: bool,
+    paths: Vec<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args_os().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => return ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("fast-sp: {message}");
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut total = Tally::default();
+    let mut total_balance = 0;
+    let mut failed = false;
+
+    for path in &options.paths {
+        if options.tally {
+            match tally(path) {
+                Ok(tally) => {
+                    print_tally(&tally, path.display());
+                    total += tally;
+                }
+                Err(e) => {
+                    eprintln!("fast-sp: {}: {e}", path.display());
+                    failed = true;
+                }
+            }
+        } else {
+            match count(path, options.counter) {
+                Ok(balance) => {
+                    println!("{balance}\t{}", path.display());
+                    total_balance += balance;
+                }
+                Err(e) => {
+                    eprintln!("fast-sp: {}: {e}", path.display());
+                    failed = true;
+                }
+            }
+        }
+    }
+
+    if options.paths.len() > 1 {
+        if options.tally {
+            print_tally(&total, "total");
+        } else {
+            println!("{total_balance}\ttotal");
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Returns `Ok(None)` when the program should exit successfully without counting.
+fn parse_args(mut args: impl Iterator<Item = OsString>) -> Result<Option<Options>, String> {
+    let mut implementation = None;
+    let mut tally = false;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some(option @ ("-i" | "--implementation")) => {
+                let name = args.next().ok_or(format!("{option} needs an argument"))?;
+                let name = name.into_string().map_err(|name| {
+                    format!("unknown implementation: {}", name.to_string_lossy())
+                })?;
+                implementation = Some(name);
+            }
+            Some("-t" | "--tally") => tally = true,
+            Some("-l" | "--list") => {
+                list_implementations();
+                return Ok(None);
+            }
+            Some("-h" | "--help") => {
+                println!("{USAGE}");
+                return Ok(None);
+            }
+            Some("--") => {
+                paths.extend(args.by_ref().map(PathBuf::from));
+            }
+            Some("-") => paths.push(PathBuf::from(arg)),
+            _ if arg.to_string_lossy().starts_with('-') => {
+                return Err(format!("unknown option: {}", arg.to_string_lossy()))
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if tally && implementation.is_some() {
+        return Err("--tally cannot be combined with --implementation".to_owned());
+    }
+
+    let name = implementation.as_deref().unwrap_or(DEFAULT_IMPLEMENTATION);
+    let counter = find(name).ok_or(format!("unknown implementation: {name} (try --list)"))?;
+    if !counter.is_supported() {
+        return Err(format!("{name} is not supported on this CPU"));
+    }
+
+    if paths.is_empty() {
+        paths.push(PathBuf::from("-"));
+    }
+
+    Ok(Some(Options {
+        counter,
+        tally,
+        paths,
+    }))
+}
+
+fn list_implementations() {
+    for counter in IMPLEMENTATIONS {
+        println!(
+            "{}\t{:?}\t{:?}{}",
+            counter.name(),
+            counter.language(),
+            counter.input(),
+            if counter.is_supported() {
+                ""
+            } else {
+                "\t(not supported on this CPU)"
+            }
+        );
+    }
+}
+
+fn count(path: &Path, counter: &dyn Counter) -> Result<i64, Error> {
+    if path == Path::new("-") {
+        file::count_reader_with(io::stdin().lock(), counter)
+    } else {
+        file::count_file(path, counter)
+    }
+}
+
+fn tally(path: &Path) -> Result<Tally, Error> {
+    if path == Path::new("-") {
+        Ok(stream::tally_reader(io::stdin().lock())?)
+    } else {
+        file::tally_file(path)
+    }
+}
+
+fn print_tally(tally: &Tally, name: impl fmt::Display) {
+    println!(
+        "{}\t{}\t{}\t{}\t{name}",
+        tally.balance(),
+        tally.s,
+        tally.p,
+        tally.other
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>, String> {
+        parse_args(args.iter().map(OsString::from))
+    }
+
+    fn error(args: impl Iterator<Item = OsString>) -> String {
+        match parse_args(args) {
+            Ok(_) => panic!("expected an error"),
+            Err(message) => message,
+        }
+    }
+
+    fn paths(options: &Options) -> Vec<&str> {
+        options.paths.iter().map(|p| p.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let options = parse(&[]).unwrap().unwrap();
+        assert_eq!(DEFAULT_IMPLEMENTATION, options.counter.name());
+        assert!(!options.tally);
+        assert_eq!(vec!["-"], paths(&options));
+    }
+
+    #[test]
+    fn test_implementation() {
+        for option in ["-i", "--implementation"] {
+            let options = parse(&[option, "rust_for_loop", "a", "b"])
+                .unwrap()
+                .unwrap();
+            assert_eq!("rust_for_loop", options.counter.name());
+            assert_eq!(vec!["a", "b"], paths(&options));
+        }
+    }
+
+    #[test]
+    fn test_tally() {
+        for option in ["-t", "--tally"] {
+            let options = parse(&[option, "a"]).unwrap().unwrap();
+            assert!(options.tally);
+            assert_eq!(vec!["a"], paths(&options));
+        }
+    }
+
+    #[test]
+    fn test_standard_input_and_double_dash() {
+        let options = parse(&["a", "-", "--", "-i", "-"]).unwrap().unwrap();
+        assert_eq!(vec!["a", "-", "-i", "-"], paths(&options));
+    }
+
+    #[test]
+    fn test_errors() {
+        let parse_error = |args: &[&str]| error(args.iter().map(OsString::from));
+        assert!(parse_error(&["-i"]).contains("needs an argument"));
+        assert!(parse_error(&["-i", "python_numpy"]).contains("unknown implementation"));
+        assert!(parse_error(&["-x"]).contains("unknown option"));
+        assert!(parse_error(&["-t", "-i", "rust_for_loop"]).contains("cannot be combined"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_arguments() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = OsStr::from_bytes(b"sp\xff.txt");
+        let options = parse_args([path.to_owned()].into_iter()).unwrap().unwrap();
+        assert_eq!(vec![PathBuf::from(path)], options.paths);
+
+        let args = [OsString::from("-i"), path.to_owned()];
+        assert!(error(args.into_iter()).contains("unknown implementation"));
+    }
+}
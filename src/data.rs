@@ -14,8 +14,47 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Random data for tests and benchmarks.
+//!
+//! build.rs generates the datasets from a seed: 2023, unless the `FAST_SP_SEED`
+//! environment variable says otherwise. Each one has a [Manifest] that records the
+//! seed, its checksum, and its expected answer.
+
+pub mod generate;
+mod manifest;
+
+pub use manifest::{fnv1a64, Manifest};
 
 /// Random ASCII 's' and 'p' bytes.
 pub const RANDOM_SP: &str = include_str!(concat!(env!("OUT_DIR"), "/random-sp.bin"));
 /// Random ASCII printable characters.
 pub const RANDOM_PRINTABLE: &str = include_str!(concat!(env!("OUT_DIR"), "/random-printable.bin"));
+
+/// The [Manifest] for [RANDOM_SP].
+pub fn random_sp_manifest() -> Manifest {
+    parse_manifest(include_str!(concat!(
+        env!("OUT_DIR"),
+        "/random-sp.manifest"
+    )))
+}
+
+/// The [Manifest] for [RANDOM_PRINTABLE].
+pub fn random_printable_manifest() -> Manifest {
+    parse_manifest(include_str!(concat!(
+        env!("OUT_DIR"),
+        "/random-printable.manifest"
+    )))
+}
+
+/// Both datasets, with their manifests, for tests that use the manifests as the
+/// expected answers.
+#[cfg(test)]
+pub(crate) fn with_manifests() -> [(&'static [u8], Manifest); 2] {
+    [
+        (RANDOM_SP.as_bytes(), random_sp_manifest()),
+        (RANDOM_PRINTABLE.as_bytes(), random_printable_manifest()),
+    ]
+}
+
+fn parse_manifest(text: &str) -> Manifest {
+    Manifest::parse(text).expect("build.rs should write valid manifests")
+}
@@ -0,0 +1,270 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Generators for datasets with more structure than [RANDOM_SP](super::RANDOM_SP)
+//! and [RANDOM_PRINTABLE](super::RANDOM_PRINTABLE). Uniformly random 's' and 'p'
+//! is the worst case for a branch predictor, and nearly all real text is much
+//! kinder (or at least different), so these show how each implementation behaves
+//! on more realistic inputs.
+
+use crate::rng::Rng;
+
+/// A generated dataset, with a name for tests and benchmarks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dataset {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+/// One of each kind of dataset, `len` bytes long, generated from `seed`.
+pub fn standard(len: usize, seed: u64) -> Vec<Dataset> {
+    let mut rng = Rng::new(seed);
+    let dataset = |name: &str, bytes| Dataset {
+        name: name.to_owned(),
+        bytes,
+    };
+
+    vec![
+        dataset("skewed_90", skewed(&mut rng, len, 0.9)),
+        dataset("skewed_99", skewed(&mut rng, len, 0.99)),
+        dataset("long_runs", runs(&mut rng, len, 1000)),
+        dataset("periodic_sp", periodic(len, b"sp")),
+        dataset("periodic_sssp", periodic(len, b"sssp")),
+        dataset("all_s", all_s(len)),
+        dataset("english", english_like(&mut rng, len)),
+        dataset("arbitrary", arbitrary(&mut rng, len)),
+    ]
+}
+
+/// For measuring how well branches are predicted: [skewed] with the probability of
+/// 's' going from 0.0 (all 'p') to 1.0 (all 's') in steps of 0.1. The closer to 0.5,
+/// the less predictable each byte is.
+pub fn probability_sweep(len: usize, seed: u64) -> Vec<Dataset> {
+    let mut rng = Rng::new(seed);
+    (0..=10)
+        .map(|tenths| {
+            let p_s = tenths as f64 / 10.0;
+            Dataset {
+                name: format!("sweep_p_{p_s:.1}"),
+                bytes: skewed(&mut rng, len, p_s),
+            }
+        })
+        .collect()
+}
+
+/// For measuring how well branches are predicted: [random_periodic], with periods
+/// from 2 to 4096 bytes. Branch predictors learn short patterns, but not long ones.
+pub fn period_sweep(len: usize, seed: u64) -> Vec<Dataset> {
+    let mut rng = Rng::new(seed);
+    (1..=12)
+        .map(|log2| {
+            let period = 1 << log2;
+            Dataset {
+                name: format!("sweep_period_{period}"),
+                bytes: random_periodic(&mut rng, len, period),
+            }
+        })
+        .collect()
+}
+
+/// 's' with probability `p_s`, and 'p' otherwise.
+pub fn skewed(rng: &mut Rng, len: usize, p_s: f64) -> Vec<u8> {
+    (0..len)
+        .map(|_| if rng.chance(p_s) { b's' } else { b'p' })
+        .collect()
+}
+
+/// Alternating runs of 's' and 'p', each between 1 and `2 * mean_run - 1` bytes
+/// long.
+pub fn runs(rng: &mut Rng, len: usize, mean_run: usize) -> Vec<u8> {
+    assert!(mean_run > 0, "runs must be at least one byte long");
+
+    let mut bytes = Vec::with_capacity(len);
+    let mut letter = *rng.choose(b"sp");
+    while bytes.len() < len {
+        let run = 1 + rng.below(2 * mean_run as u64 - 1) as usize;
+        let run = run.min(len - bytes.len());
+        bytes.resize(bytes.len() + run, letter);
+        letter = if letter == b's' { b'p' } else { b's' };
+    }
+    bytes
+}
+
+/// `pattern`, over and over.
+pub fn periodic(len: usize, pattern: &[u8]) -> Vec<u8> {
+    pattern.iter().copied().cycle().take(len).collect()
+}
+
+/// A random pattern of 's' and 'p', `period` bytes long, over and over.
+pub fn random_periodic(rng: &mut Rng, len: usize, period: usize) -> Vec<u8> {
+    periodic(len, &skewed(rng, period, 0.5))
+}
+
+/// Nothing but 's'.
+pub fn all_s(len: usize) -> Vec<u8> {
+    vec![b's'; len]
+}
+
+/// Sentences of common English words, with capitals, punctuation, and newlines.
+/// About 6% of the bytes are 's' and 2% are 'p', like in real English text.
+pub fn english_like(rng: &mut Rng, len: usize) -> Vec<u8> {
+    const WORDS: &[&str] = &[
+        "the", "of", "and", "to", "in", "is", "was", "for", "that", "it", "as", "with", "his",
+        "on", "be", "at", "by", "this", "had", "not", "are", "but", "from", "or", "have", "an",
+        "they", "which", "one", "you", "were", "her", "all", "she", "there", "would", "their",
+        "we", "him", "been", "has", "when", "who", "will", "more", "if", "out", "so", "said",
+        "what", "up", "its", "about", "into", "than", "them", "can", "only", "other", "some",
+        "could", "time", "these", "people", "may", "first", "such", "like", "over", "just",
+        "should", "because", "each", "those", "state", "still", "speak", "simple", "problem",
+        "space", "special", "process", "support", "possible", "spent", "perhaps", "stop",
+    ];
+
+    let mut bytes = Vec::with_capacity(len + 16);
+    while bytes.len() < len {
+        let n_words = 3 + rng.below(15);
+        for i in 0..n_words {
+            let word = rng.choose(WORDS).as_bytes();
+            if i == 0 {
+                bytes.push(word[0].to_ascii_uppercase());
+                bytes.extend_from_slice(&word[1..]);
+            } else {
+                bytes.push(b' ');
+                bytes.extend_from_slice(word);
+                if i + 1 < n_words && rng.chance(0.08) {
+                    bytes.push(b',');
+                }
+            }
+        }
+        bytes.push(*rng.choose(b"....?!"));
+        bytes.push(if rng.chance(0.2) { b'\n' } else { b' ' });
+    }
+    bytes.truncate(len);
+    bytes
+}
+
+/// Any byte at all, uniformly: including NUL, and 0x80 to 0xFF, which are negative
+/// as a signed `char`.
+pub fn arbitrary(rng: &mut Rng, len: usize) -> Vec<u8> {
+    (0..len).map(|_| rng.next_u64() as u8).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzing::check_implementation;
+    use crate::tests::testable;
+
+    #[test]
+    fn test_implementations_agree_on_generated_datasets() {
+        let len = if cfg!(miri) { 1_000 } else { 100_003 };
+        for dataset in standard(len, 1) {
+            assert_eq!(len, dataset.bytes.len(), "{}", dataset.name);
+            for counter in testable() {
+                for offset in [0, 3] {
+                    check_implementation(counter, &dataset.bytes, offset);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_implementations_agree_on_sweeps() {
+        let len = if cfg!(miri) { 100 } else { 10_007 };
+        for dataset in probability_sweep(len, 1)
+            .into_iter()
+            .chain(period_sweep(len, 1))
+        {
+            for counter in testable() {
+                check_implementation(counter, &dataset.bytes, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sweeps() {
+        let sweep = probability_sweep(1000, 1);
+        assert_eq!("sweep_p_0.0", sweep[0].name);
+        assert_eq!(vec![b'p'; 1000], sweep[0].bytes);
+        assert_eq!(vec![b's'; 1000], sweep[10].bytes);
+
+        let len = if cfg!(miri) { 1_000 } else { 10_000 };
+        for dataset in period_sweep(len, 1) {
+            let period: usize = dataset.name["sweep_period_".len()..].parse().unwrap();
+            let (pattern, rest) = dataset.bytes.split_at(period.min(len));
+            assert!(rest.chunks(period).all(|chunk| pattern.starts_with(chunk)));
+        }
+    }
+
+    #[test]
+    fn test_standard_is_reproducible() {
+        assert_eq!(standard(1000, 7), standard(1000, 7));
+        assert_ne!(standard(1000, 7), standard(1000, 8));
+    }
+
+    #[test]
+    fn test_known_answers() {
+        assert_eq!(vec![b's'; 5], all_s(5));
+        assert_eq!(b"spsps".to_vec(), periodic(5, b"sp"));
+        let len = if cfg!(miri) { 1_000 } else { 100_000 };
+        assert_eq!(
+            len as i64 / 2,
+            crate::rust_for_loop(&periodic(len, b"sssp"))
+        );
+    }
+
+    #[test]
+    fn test_skewed() {
+        let mut rng = Rng::new(1);
+        let bytes = skewed(&mut rng, 10_000, 0.9);
+        let n_s = bytes.iter().filter(|&&b| b == b's').count();
+        assert!((8_800..9_200).contains(&n_s), "{n_s}");
+        assert!(bytes.iter().all(|b| b"sp".contains(b)));
+    }
+
+    #[test]
+    fn test_runs() {
+        // About 1000 runs, either way.
+        let (len, mean_run) = if cfg!(miri) {
+            (10_000, 10)
+        } else {
+            (100_000, 100)
+        };
+        let mut rng = Rng::new(1);
+        let bytes = runs(&mut rng, len, mean_run);
+        let n_runs = bytes.windows(2).filter(|w| w[0] != w[1]).count() + 1;
+        assert!((800..1_200).contains(&n_runs), "{n_runs}");
+    }
+
+    #[test]
+    fn test_english_like() {
+        let len = if cfg!(miri) { 10_000 } else { 100_000 };
+        let mut rng = Rng::new(1);
+        let bytes = english_like(&mut rng, len);
+        assert!(bytes.is_ascii());
+        assert!(bytes.contains(&b'\n'));
+
+        let percent = |c| 100 * bytes.iter().filter(|&&b| b == c).count() / bytes.len();
+        assert!((4..=8).contains(&percent(b's')), "{}% 's'", percent(b's'));
+        assert!((1..=3).contains(&percent(b'p')), "{}% 'p'", percent(b'p'));
+    }
+
+    #[test]
+    fn test_arbitrary() {
+        let mut rng = Rng::new(1);
+        let bytes = arbitrary(&mut rng, 10_000);
+        assert!(bytes.contains(&0));
+        assert!(bytes.iter().any(|&b| b >= 0x80));
+    }
+}
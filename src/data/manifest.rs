@@ -0,0 +1,109 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// build.rs includes this file, so it can't have inner doc comments or use anything
+// from the rest of the crate.
+
+use std::fmt;
+
+/// What build.rs recorded about a dataset when it generated it: enough to tell
+/// whether the data is what we think it is, and what the right answer is, without
+/// trusting any implementation.
+///
+/// Manifests are written next to each dataset (e.g., `random-sp.manifest` next to
+/// `random-sp.bin`) as `key = value` lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Manifest {
+    /// The seed that the dataset was generated from.
+    pub seed: u64,
+    /// The size of the dataset, in bytes.
+    pub size: u64,
+    /// The [fnv1a64] hash of the dataset.
+    pub checksum: u64,
+    /// The number of 's' bytes.
+    pub s: u64,
+    /// The number of 'p' bytes.
+    pub p: u64,
+    /// The expected answer: `s - p`.
+    pub balance: i64,
+}
+
+impl Manifest {
+    /// Parses a manifest written by its [Display](fmt::Display) implementation.
+    /// Returns `None` if any key is missing or malformed.
+    pub fn parse(text: &str) -> Option<Manifest> {
+        let get = |key: &str| {
+            text.lines()
+                .filter_map(|line| line.split_once('='))
+                .find(|(k, _)| k.trim() == key)
+                .map(|(_, value)| value.trim())
+        };
+
+        let checksum = get("fnv1a64")?.strip_prefix("0x")?;
+        Some(Manifest {
+            seed: get("seed")?.parse().ok()?,
+            size: get("size")?.parse().ok()?,
+            checksum: u64::from_str_radix(checksum, 16).ok()?,
+            s: get("s")?.parse().ok()?,
+            p: get("p")?.parse().ok()?,
+            balance: get("balance")?.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "seed = {}", self.seed)?;
+        writeln!(f, "size = {}", self.size)?;
+        writeln!(f, "fnv1a64 = {:#018x}", self.checksum)?;
+        writeln!(f, "s = {}", self.s)?;
+        writeln!(f, "p = {}", self.p)?;
+        writeln!(f, "balance = {}", self.balance)
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`. Not cryptographic, but simple enough to
+/// compute anywhere, and good enough to notice that a dataset has changed.
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let manifest = Manifest {
+            seed: u64::MAX,
+            size: 12,
+            checksum: fnv1a64(b"sspp"),
+            s: 5,
+            p: 7,
+            balance: -2,
+        };
+        assert_eq!(Some(manifest), Manifest::parse(&manifest.to_string()));
+        assert_eq!(None, Manifest::parse("seed = 1\nsize = 2\n"));
+
+        // The test vectors from the FNV specification:
+        assert_eq!(0xcbf29ce484222325, fnv1a64(b""));
+        assert_eq!(0xaf63dc4c8601ec8c, fnv1a64(b"a"));
+    }
+}
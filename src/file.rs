@@ -0,0 +1,328 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Counting files, using `mmap` where we can.
+
+use std::ffi::NulError;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use crate::implementations::{rust_portable_simd_tally, Counter, Input, Tally};
+use crate::stream::{self, CHUNK_SIZE};
+
+/// Something went wrong while counting a file.
+#[derive(Debug)]
+pub enum Error {
+    /// Could not open, map, or read the file.
+    Io(io::Error),
+    /// The file contains a NUL byte, but the implementation expects a
+    /// NUL-terminated string.
+    Nul(NulError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::Nul(e) => write!(f, "cannot count with a NUL-terminated implementation: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Nul(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<NulError> for Error {
+    fn from(e: NulError) -> Self {
+        Error::Nul(e)
+    }
+}
+
+/// Counts the file at `path` with `counter`.
+///
+/// On 64-bit Linux, regular files are memory-mapped, and counted in one go by
+/// [Input::Sized] implementations. Everything else (pipes, character devices, files
+/// in /proc and /sys that can't be mapped, and any file on other platforms) is read
+/// [CHUNK_SIZE] bytes at a time.
+///
+/// [Input::NulTerminated] implementations always get [CHUNK_SIZE] bytes at a time,
+/// since each chunk is copied into a [CString](std::ffi::CString) first.
+pub fn count_file<P: AsRef<Path>>(path: P, counter: &dyn Counter) -> Result<i64, Error> {
+    let max_chunk = match counter.input() {
+        Input::Sized => usize::MAX,
+        Input::NulTerminated => CHUNK_SIZE,
+    };
+
+    let mut result = 0;
+    for_each_chunk_in_file(path.as_ref(), max_chunk, |chunk| {
+        result += counter.count_bytes(chunk)?;
+        Ok(())
+    })?;
+    Ok(result)
+}
+
+/// Tallies the file at `path`. Files are read the same way as [count_file].
+pub fn tally_file<P: AsRef<Path>>(path: P) -> Result<Tally, Error> {
+    let mut result = Tally::default();
+    for_each_chunk_in_file(path.as_ref(), usize::MAX, |chunk| {
+        result += rust_portable_simd_tally(chunk);
+        Ok(())
+    })?;
+    Ok(result)
+}
+
+/// Counts everything in `reader` with `counter`, [CHUNK_SIZE] bytes at a time.
+/// Use this for input that isn't a file, like standard input.
+pub fn count_reader_with<R: Read>(reader: R, counter: &dyn Counter) -> Result<i64, Error> {
+    let mut result = 0;
+    stream::for_each_chunk(BufReader::with_capacity(CHUNK_SIZE, reader), |chunk| {
+        result += counter.count_bytes(chunk)?;
+        Ok::<_, Error>(())
+    })?;
+    Ok(result)
+}
+
+/// Calls `f` with every chunk of the file at `path`. Chunks of a memory-mapped file
+/// are at most `max_chunk` bytes; chunks that are read are at most [CHUNK_SIZE].
+fn for_each_chunk_in_file<F>(path: &Path, max_chunk: usize, mut f: F) -> Result<(), Error>
+where
+    F: FnMut(&[u8]) -> Result<(), Error>,
+{
+    let file = File::open(path)?;
+
+    #[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+    {
+        let metadata = file.metadata()?;
+        if metadata.is_file() && metadata.len() > 0 {
+            // Some files can't be mapped, even though they claim to be regular files
+            // with a size (e.g., most of /sys fails with ENODEV). Read those instead.
+            if let Ok(map) = mmap::Mmap::map(&file, metadata.len()) {
+                return map.as_slice().chunks(max_chunk).try_for_each(f);
+            }
+        }
+    }
+    #[cfg(not(all(target_os = "linux", target_pointer_width = "64")))]
+    let _ = max_chunk;
+
+    stream::for_each_chunk(BufReader::with_capacity(CHUNK_SIZE, file), &mut f)
+}
+
+// off_t is only 64 bits (matching `offset`) on 64-bit Linux.
+#[cfg(all(target_os = "linux", target_pointer_width = "64"))]
+mod mmap {
+    use std::ffi::{c_int, c_void};
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    // From <sys/mman.h>
+    const PROT_READ: c_int = 0x1;
+    const MAP_PRIVATE: c_int = 0x02;
+    const MADV_SEQUENTIAL: c_int = 2;
+    const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            length: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, length: usize) -> c_int;
+        fn madvise(addr: *mut c_void, length: usize, advice: c_int) -> c_int;
+    }
+
+    /// A read-only, private mapping of an entire file. Unmapped when dropped.
+    pub struct Mmap {
+        ptr: *mut c_void,
+        len: usize,
+    }
+
+    impl Mmap {
+        /// Maps the first `len` bytes of `file`. `len` must be nonzero.
+        pub fn map(file: &File, len: u64) -> io::Result<Mmap> {
+            let len = usize::try_from(len)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large"))?;
+
+            let ptr = unsafe {
+                mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    PROT_READ,
+                    MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr == MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+
+            // We read the whole thing front to back, so ask for aggressive read-ahead.
+            // This is only a hint, so it doesn't matter if it fails.
+            unsafe { madvise(ptr, len, MADV_SEQUENTIAL) };
+
+            Ok(Mmap { ptr, len })
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            // The mapping is valid and readable for len bytes until it is dropped.
+            unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for Mmap {
+        fn drop(&mut self) {
+            unsafe { munmap(self.ptr, self.len) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::testable;
+    use crate::{data, find, rust_for_loop};
+
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    /// A file in the temporary directory that is deleted when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("fast-sp-test-{}-{name}", std::process::id()));
+            fs::write(&path, contents).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "Miri can't mmap")]
+    fn test_count_file() {
+        let file = TempFile::new("random-printable", data::RANDOM_PRINTABLE.as_bytes());
+        let expected = data::random_printable_manifest().balance;
+
+        for counter in testable() {
+            let actual = count_file(&file.0, counter).unwrap();
+            assert_eq!(expected, actual, "{}", counter.name());
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "Miri can't mmap")]
+    fn test_tally_file() {
+        let file = TempFile::new("random-printable-tally", data::RANDOM_PRINTABLE.as_bytes());
+        let manifest = data::random_printable_manifest();
+        let tally = tally_file(&file.0).unwrap();
+        assert_eq!((manifest.s, manifest.p), (tally.s, tally.p));
+        assert_eq!(manifest.size, tally.total());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+    fn test_count_reader_with() {
+        let bytes = data::RANDOM_SP.as_bytes();
+        let expected = data::random_sp_manifest().balance;
+
+        for counter in testable() {
+            let actual = count_reader_with(bytes, counter).unwrap();
+            assert_eq!(expected, actual, "{}", counter.name());
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "Miri can't open files")]
+    fn test_empty_file() {
+        let file = TempFile::new("empty", b"");
+        for counter in testable() {
+            assert_eq!(
+                0,
+                count_file(&file.0, counter).unwrap(),
+                "{}",
+                counter.name()
+            );
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "Miri can't call the C implementations")]
+    fn test_nul_terminated_implementation_with_nul_in_file() {
+        let file = TempFile::new("with-nul", b"ss\0p");
+
+        let counter = find("c_original").unwrap();
+        assert!(matches!(count_file(&file.0, counter), Err(Error::Nul(_))));
+
+        let counter = find("rust_for_loop").unwrap();
+        assert_eq!(1, count_file(&file.0, counter).unwrap());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "Miri can't open files")]
+    fn test_missing_file() {
+        let counter = find("rust_for_loop").unwrap();
+        match count_file("/this/file/does/not/exist", counter) {
+            Err(Error::Io(e)) => assert_eq!(ErrorKind::NotFound, e.kind()),
+            other => panic!("expected an I/O error, got {other:?}"),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[cfg_attr(miri, ignore = "Miri can't open files")]
+    fn test_special_files() {
+        let counter = find("rust_for_loop").unwrap();
+
+        // A character device:
+        assert_eq!(0, count_file("/dev/null", counter).unwrap());
+
+        // A "regular" file that says it is empty, but isn't:
+        let expected = rust_for_loop(&fs::read("/proc/self/cmdline").unwrap());
+        assert_eq!(expected, count_file("/proc/self/cmdline", counter).unwrap());
+
+        // A "regular" file that has a size, but can't be mapped:
+        let path = "/sys/devices/system/cpu/online";
+        if let Ok(bytes) = fs::read(path) {
+            assert_ne!(0, fs::metadata(path).unwrap().len());
+            assert_eq!(rust_for_loop(&bytes), count_file(path, counter).unwrap());
+            assert_eq!(rust_portable_simd_tally(&bytes), tally_file(path).unwrap());
+        }
+    }
+}
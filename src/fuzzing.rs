@@ -0,0 +1,123 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Entry points for the fuzz targets in `fuzz/`. The tests replay everything in
+//! `fuzz/corpus/` through the same functions, so a crash that the fuzzer finds
+//! can be turned into a regression test by copying it into the corpus.
+
+use std::ffi::CString;
+
+use crate::implementations::{rust_for_loop, supported, Counter, Input};
+
+/// Checks that every implementation supported on this CPU agrees with
+/// [rust_for_loop] on `data`.
+///
+/// The first byte of `data` is not counted: it says where the input starts in
+/// memory, relative to a 64-byte boundary (only its low 6 bits are used). That way
+/// the fuzzer can find bugs in the unaligned prefixes and suffixes of the SIMD
+/// implementations, as well as in their handling of the length.
+///
+/// # Panics
+///
+/// Panics if any implementation gets a different answer.
+pub fn check_implementations(data: &[u8]) {
+    let (offset, bytes) = match data.split_first() {
+        Some((&offset, bytes)) => (offset as usize % 64, bytes),
+        None => (0, data),
+    };
+
+    for counter in supported() {
+        check_implementation(counter, bytes, offset);
+    }
+}
+
+/// Checks that `counter` agrees with [rust_for_loop] on `bytes`, when they start
+/// `offset` bytes past a 64-byte boundary.
+///
+/// # Panics
+///
+/// Panics if `counter` gets a different answer.
+pub fn check_implementation(counter: &dyn Counter, bytes: &[u8], offset: usize) {
+    let (expected, actual) = expected_and_actual(counter, bytes, offset);
+    assert_eq!(
+        expected,
+        actual,
+        "{} disagrees with rust_for_loop on b\"{}\" ({} bytes, offset {offset})",
+        counter.name(),
+        bytes.escape_ascii(),
+        bytes.len(),
+    );
+}
+
+/// Returns what [rust_for_loop] and `counter` get for `bytes`, when they start
+/// `offset` bytes past a 64-byte boundary.
+///
+/// Implementations that take a NUL-terminated string only count up to the first
+/// NUL.
+pub(crate) fn expected_and_actual(
+    counter: &dyn Counter,
+    bytes: &[u8],
+    offset: usize,
+) -> (i64, i64) {
+    match counter.input() {
+        Input::Sized => {
+            let mut buffer = vec![0; bytes.len() + offset + 64];
+            let start = (64 - buffer.as_ptr() as usize % 64) % 64 + offset;
+            let input = &mut buffer[start..start + bytes.len()];
+            input.copy_from_slice(bytes);
+
+            (rust_for_loop(input), counter.count_bytes(input).unwrap())
+        }
+        Input::NulTerminated => {
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            let input = CString::new(&bytes[..end]).unwrap();
+
+            (rust_for_loop(&bytes[..end]), counter.count(&input))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    #[cfg_attr(
+        miri,
+        ignore = "Miri can't read directories, or call the C implementations"
+    )]
+    fn test_replay_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/implementations");
+        let mut n_inputs = 0;
+        for entry in fs::read_dir(&corpus).unwrap() {
+            let path = entry.unwrap().path();
+            let data = fs::read(&path).unwrap();
+            let result = std::panic::catch_unwind(|| check_implementations(&data));
+            assert!(result.is_ok(), "{}", path.display());
+            n_inputs += 1;
+        }
+        assert!(n_inputs > 0, "{} is empty", corpus.display());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "Miri can't call the C implementations")]
+    fn test_empty_input() {
+        check_implementations(b"");
+        check_implementations(b"\x07");
+    }
+}
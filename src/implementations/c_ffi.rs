@@ -15,63 +15,90 @@
 
 use std::ffi::{c_char, CStr};
 
+use super::Tally;
+
 macro_rules! define_ffi {
-    ($(#[$meta:meta])* $name: ident => $link_name: ident *const c_char) => {
+    ($(#[$meta:meta])* $name: ident => $link_name: ident *const c_char -> $ret: ty) => {
         $(#[$meta])*
         #[inline(always)]
-        pub fn $name(s: &CStr) -> isize {
+        pub fn $name(s: &CStr) -> i64 {
             // Tiny wrapper that changes Rust's borrowed CStr and converts it into C's const char*.
             // This will link to libcount.a and use its count_c function.
             // The scope of this external symbol is entirely internal to this function.
             #[link(name = "count", kind = "static")]
             extern "C" {
-                fn $link_name(s: *const c_char) -> isize;
+                fn $link_name(s: *const c_char) -> $ret;
             }
 
-            unsafe { $link_name(s.as_ptr()) }
+            unsafe { $link_name(s.as_ptr()).into() }
         }
     };
 
-    ($(#[$meta:meta])* $name: ident => $link_name: ident usize) => {
+    ($(#[$meta:meta])* $name: ident => $link_name: ident usize -> Tally) => {
         $(#[$meta])*
         #[inline(always)]
-        pub fn $name(s: &CStr) -> isize {
+        pub fn $name(s: &[u8]) -> Tally {
             // The scope of this external symbol is entirely internal to this function.
             #[link(name = "count", kind = "static")]
             extern "C" {
-                fn $link_name(s: *const c_char, n: usize) -> i32;
+                fn $link_name(s: *const c_char, n: usize) -> Tally;
             }
 
-            let s = s.to_bytes();
-            unsafe { $link_name(s.as_ptr() as *const c_char, s.len()) as isize }
+            unsafe { $link_name(s.as_ptr() as *const c_char, s.len()) }
+        }
+    };
+
+    ($(#[$meta:meta])* $name: ident => $link_name: ident usize -> $ret: ty) => {
+        $(#[$meta])*
+        #[inline(always)]
+        pub fn $name(s: &[u8]) -> i64 {
+            // The scope of this external symbol is entirely internal to this function.
+            #[link(name = "count", kind = "static")]
+            extern "C" {
+                fn $link_name(s: *const c_char, n: usize) -> $ret;
+            }
+
+            unsafe { $link_name(s.as_ptr() as *const c_char, s.len()).into() }
         }
     };
 }
 
 define_ffi!(
     /// Owen's original implementation written in C. See c/original.c
-    c_original => run_switches *const c_char
+    c_original => run_switches *const c_char -> i64
 );
 
 define_ffi!(
     /// Count implementation written in C. See c/while-not-zero.c
-    c_while_loop => while_not_zero *const c_char
+    c_while_loop => while_not_zero *const c_char -> i64
 );
 
 define_ffi!(
     /// Owen's implementation, with explicit size (does not check for null terminator).
     /// See c/with-explicit-size.c
-    c_for_loop => with_explicit_size usize
+    c_for_loop => with_explicit_size usize -> i64
 );
 
 define_ffi!(
     /// Using a state machine approach from <https://github.com/robertdavidgraham/wc2>
     /// See c/state-machine.c
-    c_state_machine => c_state_machine usize
+    c_state_machine => c_state_machine usize -> i64
 );
 
 define_ffi!(
     /// Like the [c_state_machine], but without the state machine.
     /// See c/count-machine.c
-    c_count_machine => c_count_machine usize
+    c_count_machine => c_count_machine usize -> i64
+);
+
+define_ffi!(
+    /// Like [c_state_machine], but returns the full [Tally].
+    /// See c/state-machine.c
+    c_state_machine_tally => c_state_machine_tally usize -> Tally
+);
+
+define_ffi!(
+    /// Like [c_count_machine], but returns the full [Tally].
+    /// See c/count-machine.c
+    c_count_machine_tally => c_count_machine_tally usize -> Tally
 );
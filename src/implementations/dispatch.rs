@@ -0,0 +1,179 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Picks the widest SIMD instructions that the CPU supports at runtime, so that the
+//! same binary uses AVX2 where it's available, and still runs where it isn't.
+
+#[cfg(feature = "nightly-simd")]
+use std::simd::{LaneCount, Simd, SimdInt, SimdPartialEq, SupportedLaneCount};
+
+#[cfg(feature = "nightly-simd")]
+use super::rust_portable_simd::_count_scalar;
+use super::rust_portable_simd::rust_portable_simd;
+
+/// An instruction set that [rust_dispatch] can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Isa {
+    /// [rust_portable_simd], compiled for whatever the target supports at build time.
+    Portable,
+    /// 16 bytes at a time, with SSE2.
+    Sse2,
+    /// 32 bytes at a time, with AVX2.
+    Avx2,
+}
+
+impl Isa {
+    /// Every instruction set, from narrowest to widest.
+    pub const ALL: [Isa; 3] = [Isa::Portable, Isa::Sse2, Isa::Avx2];
+
+    /// The widest instruction set that this CPU supports.
+    pub fn detect() -> Isa {
+        Isa::ALL
+            .into_iter()
+            .rev()
+            .find(|isa| isa.is_supported())
+            .unwrap_or(Isa::Portable)
+    }
+
+    /// Whether this CPU supports the instruction set.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Isa::Portable => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Isa::Sse2 => is_x86_feature_detected!("sse2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Isa::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            Isa::Sse2 | Isa::Avx2 => false,
+        }
+    }
+
+    /// Counts using this instruction set, or returns `None` if this CPU doesn't
+    /// support it.
+    pub fn count(self, bytes: &[u8]) -> Option<i64> {
+        if !self.is_supported() {
+            return None;
+        }
+
+        match self {
+            Isa::Portable => Some(rust_portable_simd(bytes)),
+            // Safety: we just checked that the CPU supports these instructions.
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Isa::Sse2 => Some(unsafe { count_sse2(bytes) }),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Isa::Avx2 => Some(unsafe { count_avx2(bytes) }),
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            Isa::Sse2 | Isa::Avx2 => unreachable!(),
+        }
+    }
+}
+
+/// Counts with the widest SIMD instructions that this CPU supports. See [Isa].
+pub fn rust_dispatch(bytes: &[u8]) -> i64 {
+    Isa::detect()
+        .count(bytes)
+        .expect("detected instruction set should be supported")
+}
+
+#[cfg(all(
+    feature = "nightly-simd",
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[target_feature(enable = "sse2")]
+unsafe fn count_sse2(bytes: &[u8]) -> i64 {
+    _count_simd::<16>(bytes)
+}
+
+#[cfg(all(
+    feature = "nightly-simd",
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[target_feature(enable = "avx2")]
+unsafe fn count_avx2(bytes: &[u8]) -> i64 {
+    _count_simd::<32>(bytes)
+}
+
+// Without portable_simd, use the hand-written intrinsics instead.
+#[cfg(all(
+    not(feature = "nightly-simd"),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+use super::{x86_avx2::count_avx2, x86_sse2::count_sse2};
+
+// The same as rust_portable_simd, but with N lanes. This must be inlined into the
+// #[target_feature] functions above to be compiled with their instructions.
+#[cfg(feature = "nightly-simd")]
+#[inline(always)]
+fn _count_simd<const N: usize>(bytes: &[u8]) -> i64
+where
+    LaneCount<N>: SupportedLaneCount,
+{
+    let (prefix, middle, suffix) = bytes.as_simd::<N>();
+
+    let s = Simd::<u8, N>::splat(b's');
+    let p = Simd::<u8, N>::splat(b'p');
+
+    let mut result = 0;
+    for &window in middle {
+        let neg_ss = window.simd_eq(s).to_int();
+        let neg_ps = window.simd_eq(p).to_int();
+        let pairwise = neg_ps - neg_ss;
+
+        result += pairwise.reduce_sum() as i64;
+    }
+
+    _count_scalar(prefix) + result + _count_scalar(suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{count, data, rust_for_loop};
+
+    #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+    fn test_every_supported_isa() {
+        assert!(Isa::Portable.is_supported());
+
+        for (bytes, manifest) in data::with_manifests() {
+            let expected = manifest.balance;
+
+            for isa in Isa::ALL {
+                match isa.count(bytes) {
+                    Some(actual) => assert_eq!(expected, actual, "{isa:?}"),
+                    None => assert!(!isa.is_supported(), "{isa:?}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+    fn test_awkward_lengths() {
+        let bytes = data::RANDOM_SP.as_bytes();
+        for len in [0, 1, 15, 16, 17, 31, 32, 33, 63, 64, 65, 1000] {
+            let bytes = &bytes[..len];
+            for isa in Isa::ALL.into_iter().filter(|isa| isa.is_supported()) {
+                assert_eq!(Some(rust_for_loop(bytes)), isa.count(bytes), "{isa:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_count() {
+        assert!(Isa::detect().is_supported());
+        assert_eq!(3, count(b"ssssspssspssspp.pssspspppsppppsp"));
+    }
+}
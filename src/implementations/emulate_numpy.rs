@@ -13,26 +13,45 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::ffi::CStr;
+use std::mem::MaybeUninit;
+#[cfg(feature = "nightly-simd")]
 use std::simd::SimdPartialEq;
 
-pub fn rust_emulate_numpy(s: &CStr) -> isize {
-    let bytes = s.to_bytes();
+use super::Tally;
 
+pub fn rust_emulate_numpy(bytes: &[u8]) -> i64 {
     let ps = vec_eq(bytes, b'p');
-    let num_ps = nonzeros(&ps) as isize;
+    let num_ps = nonzeros(&ps) as i64;
 
     let ss = vec_eq(bytes, b's');
-    let num_ss = nonzeros(&ss) as isize;
+    let num_ss = nonzeros(&ss) as i64;
 
     num_ss - num_ps
 }
 
+/// Like [rust_emulate_numpy], but returns the full [Tally].
+pub fn rust_emulate_numpy_tally(bytes: &[u8]) -> Tally {
+    let ps = vec_eq(bytes, b'p');
+    let num_ps = nonzeros(&ps) as u64;
+
+    let ss = vec_eq(bytes, b's');
+    let num_ss = nonzeros(&ss) as u64;
+
+    Tally {
+        s: num_ss,
+        p: num_ps,
+        other: bytes.len() as u64 - num_ss - num_ps,
+    }
+}
+
 #[inline(never)]
 pub fn vec_eq(s: &[u8], value: u8) -> Vec<bool> {
     s.iter().map(|&c| c == value).collect()
 }
 
+/// Unsound: treats uninitialized memory as `[bool]`. Use [vec_eq_simd_sound]
+/// instead, which is just as fast.
+#[deprecated(note = "unsound, use vec_eq_simd_sound")]
 #[inline(never)]
 pub fn vec_eq_simd(input: &[u8], value: u8) -> Vec<bool> {
     const N: usize = 32;
@@ -58,10 +77,12 @@ pub fn vec_eq_simd(input: &[u8], value: u8) -> Vec<bool> {
     buffer
 }
 
+/// Use [vec_eq_only_simd_sound] if `buffer` might not be initialized.
 pub fn vec_eq_only_simd(input: &[u8], buffer: &mut [bool], value: u8) {
     _vec_eq_fast::<32>(input, buffer, value)
 }
 
+#[cfg(feature = "nightly-simd")]
 #[inline]
 fn _vec_eq_fast<const N: usize>(input: &[u8], buffer: &mut [bool], value: u8)
 where
@@ -82,6 +103,21 @@ where
     }
 }
 
+// Without portable_simd, a plain loop, which LLVM autovectorizes anyway.
+#[cfg(not(feature = "nightly-simd"))]
+#[inline]
+fn _vec_eq_fast<const N: usize>(input: &[u8], buffer: &mut [bool], value: u8) {
+    assert_eq!(input.len() % N, 0);
+    assert_eq!(input.len(), buffer.len());
+
+    for (output, &byte) in buffer.iter_mut().zip(input) {
+        *output = byte == value;
+    }
+}
+
+/// Unsound: returns uninitialized `bool`s. Use [vec_eq_do_nothing_but_allocate_sound]
+/// instead.
+#[deprecated(note = "unsound, use vec_eq_do_nothing_but_allocate_sound")]
 #[allow(clippy::uninit_vec)]
 pub fn vec_eq_do_nothing_but_allocate(input: &[u8], _value: u8) -> Vec<bool> {
     let mut buffer = Vec::<bool>::with_capacity(input.len());
@@ -104,7 +140,120 @@ pub fn vec_eq_only_prefix(input: &[u8], value: u8) -> Vec<bool> {
     buffer
 }
 
+/// Like [vec_eq_simd], but sound: the SIMD loop writes into the vector's spare
+/// capacity as `MaybeUninit<bool>`, and the length is only set once every element
+/// has been initialized.
+#[inline(never)]
+pub fn vec_eq_simd_sound(input: &[u8], value: u8) -> Vec<bool> {
+    const N: usize = 32;
+
+    let mut buffer = Vec::<bool>::with_capacity(input.len());
+
+    let n_initial_bytes = input.len() % N;
+    let (prefix, rest) = buffer.spare_capacity_mut()[..input.len()].split_at_mut(n_initial_bytes);
+    for (output, &byte) in prefix.iter_mut().zip(&input[..n_initial_bytes]) {
+        output.write(byte == value);
+    }
+
+    _vec_eq_fast_sound::<N>(&input[n_initial_bytes..], rest, value);
+
+    // Safety: the two loops above initialized the first input.len() elements.
+    unsafe {
+        buffer.set_len(input.len());
+    }
+
+    buffer
+}
+
+/// Like [vec_eq_only_simd], but writes into (possibly) uninitialized memory.
+pub fn vec_eq_only_simd_sound(input: &[u8], buffer: &mut [MaybeUninit<bool>], value: u8) {
+    _vec_eq_fast_sound::<32>(input, buffer, value)
+}
+
+#[cfg(feature = "nightly-simd")]
+#[inline]
+fn _vec_eq_fast_sound<const N: usize>(input: &[u8], buffer: &mut [MaybeUninit<bool>], value: u8)
+where
+    std::simd::LaneCount<N>: std::simd::SupportedLaneCount,
+{
+    use std::simd::Simd;
+
+    assert_eq!(input.len() % N, 0);
+    assert_eq!(input.len(), buffer.len());
+
+    let one = Simd::<i8, N>::splat(1);
+    let value = Simd::<u8, N>::splat(value);
+    for (output_chunk, input_chunk) in buffer.chunks_exact_mut(N).zip(input.chunks_exact(N)) {
+        let input_chunk = Simd::<u8, N>::from_slice(input_chunk);
+        let result = input_chunk.simd_eq(value).to_int() & one;
+        // Safety: output_chunk is exactly N bytes long, and every byte is either 0
+        // or 1, so it's a valid bool. MaybeUninit<bool> has the same layout as bool.
+        unsafe {
+            std::ptr::write_unaligned(output_chunk.as_mut_ptr().cast::<Simd<i8, N>>(), result);
+        }
+    }
+}
+
+#[cfg(not(feature = "nightly-simd"))]
+#[inline]
+fn _vec_eq_fast_sound<const N: usize>(input: &[u8], buffer: &mut [MaybeUninit<bool>], value: u8) {
+    assert_eq!(input.len() % N, 0);
+    assert_eq!(input.len(), buffer.len());
+
+    for (output, &byte) in buffer.iter_mut().zip(input) {
+        output.write(byte == value);
+    }
+}
+
+/// Like [vec_eq_do_nothing_but_allocate], but sound, because the elements are
+/// honestly uninitialized.
+pub fn vec_eq_do_nothing_but_allocate_sound(input: &[u8], _value: u8) -> Vec<MaybeUninit<bool>> {
+    let mut buffer = Vec::<MaybeUninit<bool>>::with_capacity(input.len());
+
+    // Safety: MaybeUninit doesn't need to be initialized.
+    unsafe {
+        buffer.set_len(input.len());
+    }
+    buffer
+}
+
 #[inline(never)]
 pub fn nonzeros(s: &[bool]) -> usize {
     s.iter().map(|&b| b as usize).sum()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+
+    #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+    fn test_vec_eq_simd_sound() {
+        let bytes = data::RANDOM_PRINTABLE.as_bytes();
+        for len in [0, 1, 31, 32, 33, 64, 1000, bytes.len()] {
+            let bytes = &bytes[..len];
+            assert_eq!(vec_eq(bytes, b's'), vec_eq_simd_sound(bytes, b's'), "{len}");
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+    fn test_vec_eq_only_simd_sound() {
+        let bytes = &data::RANDOM_SP.as_bytes()[..32 * 10];
+        let mut buffer = [MaybeUninit::uninit(); 32 * 10];
+        vec_eq_only_simd_sound(bytes, &mut buffer, b'p');
+
+        let buffer: Vec<bool> = buffer.iter().map(|b| unsafe { b.assume_init() }).collect();
+        assert_eq!(vec_eq(bytes, b'p'), buffer);
+    }
+
+    #[test]
+    fn test_vec_eq_do_nothing_but_allocate_sound() {
+        let bytes = b"sspspss";
+        assert_eq!(
+            bytes.len(),
+            vec_eq_do_nothing_but_allocate_sound(bytes, b's').len()
+        );
+    }
+}
@@ -14,13 +14,34 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 mod c_ffi;
+mod dispatch;
 mod emulate_numpy;
+mod registry;
 mod rust_for_loop;
 mod rust_iter;
 mod rust_portable_simd;
+mod tally;
+mod weighted;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86_avx2;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86_sad;
+#[cfg(all(
+    not(feature = "nightly-simd"),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+mod x86_sse2;
 
 pub use c_ffi::*;
+pub use dispatch::{rust_dispatch, Isa};
 pub use emulate_numpy::*;
+pub use registry::{
+    find, supported, Counter, Implementation, Input, Kernel, Language, IMPLEMENTATIONS,
+};
 pub use rust_for_loop::rust_for_loop;
 pub use rust_iter::rust_iter;
-pub use rust_portable_simd::rust_portable_simd;
+pub use rust_portable_simd::{rust_portable_simd, rust_portable_simd_tally};
+pub use tally::Tally;
+pub use weighted::*;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use x86_avx2::rust_x86_avx2;
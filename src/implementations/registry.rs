@@ -0,0 +1,209 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A registry of every implementation, so that tests, benchmarks and other tools
+//! can iterate over all of them without listing them by hand.
+
+use std::ffi::{CStr, CString, NulError};
+
+/// The language an implementation is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    C,
+    Rust,
+}
+
+/// What an implementation needs to know about its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// Scans until it finds the NUL-terminator.
+    NulTerminated,
+    /// Uses the length of the input, and never looks at the NUL-terminator.
+    Sized,
+}
+
+/// Something that can count the number of 's' minus the number of 'p' in its input.
+pub trait Counter: Sync {
+    /// The name of the implementation, e.g., `"rust_portable_simd"`.
+    fn name(&self) -> &'static str;
+
+    /// The language the implementation is written in.
+    fn language(&self) -> Language;
+
+    /// The function that does the actual counting. Call it directly to use the
+    /// `&[u8]` entry point of a [`Kernel::Sized`] implementation, or to avoid dynamic
+    /// dispatch on every call.
+    fn kernel(&self) -> Kernel;
+
+    /// Whether this CPU can run the implementation.
+    fn is_supported(&self) -> bool {
+        true
+    }
+
+    /// What the implementation needs from its input.
+    fn input(&self) -> Input {
+        match self.kernel() {
+            Kernel::NulTerminated(_) => Input::NulTerminated,
+            Kernel::Sized(_) => Input::Sized,
+        }
+    }
+
+    /// Returns the number of 's' minus the number of 'p' in `s`.
+    #[inline(always)]
+    fn count(&self, s: &CStr) -> i64 {
+        match self.kernel() {
+            Kernel::NulTerminated(count) => count(s),
+            Kernel::Sized(count) => count(s.to_bytes()),
+        }
+    }
+
+    /// Returns the number of 's' minus the number of 'p' in `bytes`.
+    ///
+    /// [`Input::NulTerminated`] implementations have to copy `bytes` into a
+    /// [`CString`] first, so this fails if `bytes` contains a NUL byte.
+    #[inline(always)]
+    fn count_bytes(&self, bytes: &[u8]) -> Result<i64, NulError> {
+        match self.kernel() {
+            Kernel::NulTerminated(count) => Ok(count(&CString::new(bytes)?)),
+            Kernel::Sized(count) => Ok(count(bytes)),
+        }
+    }
+}
+
+/// The function that does the actual counting.
+#[derive(Clone, Copy)]
+pub enum Kernel {
+    NulTerminated(fn(&CStr) -> i64),
+    Sized(fn(&[u8]) -> i64),
+}
+
+/// One of the implementations in this crate.
+pub struct Implementation {
+    name: &'static str,
+    language: Language,
+    kernel: Kernel,
+    is_supported: fn() -> bool,
+}
+
+impl Counter for Implementation {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn language(&self) -> Language {
+        self.language
+    }
+
+    fn kernel(&self) -> Kernel {
+        self.kernel
+    }
+
+    fn is_supported(&self) -> bool {
+        (self.is_supported)()
+    }
+}
+
+/// Every implementation in this crate, including those this CPU can't run. This is
+/// the one place to add a new implementation: the tests, the benchmarks, and the
+/// command-line tool all iterate over it.
+pub static IMPLEMENTATIONS: &[&dyn Counter] = &[
+    &Implementation {
+        name: "c_original",
+        language: Language::C,
+        kernel: Kernel::NulTerminated(super::c_original),
+        is_supported: always,
+    },
+    &Implementation {
+        name: "c_for_loop",
+        language: Language::C,
+        kernel: Kernel::Sized(super::c_for_loop),
+        is_supported: always,
+    },
+    &Implementation {
+        name: "c_while_loop",
+        language: Language::C,
+        kernel: Kernel::NulTerminated(super::c_while_loop),
+        is_supported: always,
+    },
+    &Implementation {
+        name: "rust_emulate_numpy",
+        language: Language::Rust,
+        kernel: Kernel::Sized(super::rust_emulate_numpy),
+        is_supported: always,
+    },
+    &Implementation {
+        name: "rust_for_loop",
+        language: Language::Rust,
+        kernel: Kernel::Sized(super::rust_for_loop),
+        is_supported: always,
+    },
+    &Implementation {
+        name: "rust_iter",
+        language: Language::Rust,
+        kernel: Kernel::Sized(super::rust_iter),
+        is_supported: always,
+    },
+    &Implementation {
+        name: "rust_portable_simd",
+        language: Language::Rust,
+        kernel: Kernel::Sized(super::rust_portable_simd),
+        is_supported: always,
+    },
+    &Implementation {
+        name: "rust_dispatch",
+        language: Language::Rust,
+        kernel: Kernel::Sized(super::rust_dispatch),
+        is_supported: always,
+    },
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    &Implementation {
+        name: "rust_x86_avx2",
+        language: Language::Rust,
+        kernel: Kernel::Sized(super::rust_x86_avx2),
+        is_supported: || is_x86_feature_detected!("avx2"),
+    },
+    &Implementation {
+        name: "c_state_machine",
+        language: Language::C,
+        kernel: Kernel::Sized(super::c_state_machine),
+        is_supported: always,
+    },
+    &Implementation {
+        name: "c_count_machine",
+        language: Language::C,
+        kernel: Kernel::Sized(super::c_count_machine),
+        is_supported: always,
+    },
+];
+
+fn always() -> bool {
+    true
+}
+
+/// Finds an implementation by its name.
+pub fn find(name: &str) -> Option<&'static dyn Counter> {
+    IMPLEMENTATIONS
+        .iter()
+        .copied()
+        .find(|counter| counter.name() == name)
+}
+
+/// Every implementation that this CPU can run.
+pub fn supported() -> impl Iterator<Item = &'static dyn Counter> {
+    IMPLEMENTATIONS
+        .iter()
+        .copied()
+        .filter(|counter| counter.is_supported())
+}
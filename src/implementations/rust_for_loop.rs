@@ -13,14 +13,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::ffi::CStr;
-
 /// Counts using an explicit for-loop with mutable state.
 ///
 /// See assembly in Compiler Explorer: <https://godbolt.org/z/P1jGzroqY>
-pub fn rust_for_loop(s: &CStr) -> isize {
+pub fn rust_for_loop(s: &[u8]) -> i64 {
     let mut result = 0;
-    for &c in s.to_bytes() {
+    for &c in s {
         if c == b's' {
             result += 1;
         } else if c == b'p' {
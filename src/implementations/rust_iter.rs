@@ -13,14 +13,11 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::ffi::CStr;
-
 /// Counts using Rust iterators.
 ///
 /// See assembly in Compiler Explorer: <https://godbolt.org/z/jja8PMqTr>
-pub fn rust_iter(s: &CStr) -> isize {
-    s.to_bytes()
-        .iter()
+pub fn rust_iter(s: &[u8]) -> i64 {
+    s.iter()
         .map(|c| match c {
             b's' => 1,
             b'p' => -1,
@@ -13,14 +13,16 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::ffi::CStr;
+#[cfg(feature = "nightly-simd")]
 use std::simd::{u8x16, SimdInt, SimdPartialEq};
 
+use super::Tally;
+
 /// Counts using portable_simd.
 ///
 /// See assembly in Compiler Explorer: <https://godbolt.org/z/TPj7KTsaY>
-pub fn rust_portable_simd(s: &CStr) -> isize {
-    let bytes = s.to_bytes();
+#[cfg(feature = "nightly-simd")]
+pub fn rust_portable_simd(bytes: &[u8]) -> i64 {
     let (prefix, middle, suffix) = bytes.as_simd();
 
     let s = u8x16::splat(b's');
@@ -32,15 +34,86 @@ pub fn rust_portable_simd(s: &CStr) -> isize {
         let neg_ps = window.simd_eq(p).to_int();
         let pairwise = neg_ps - neg_ss;
 
-        result += pairwise.reduce_sum() as isize;
+        result += pairwise.reduce_sum() as i64;
     }
 
     _count_scalar(prefix) + result + _count_scalar(suffix)
 }
 
+/// Like [rust_portable_simd], but returns the full [Tally].
+#[cfg(feature = "nightly-simd")]
+pub fn rust_portable_simd_tally(bytes: &[u8]) -> Tally {
+    let (prefix, middle, suffix) = bytes.as_simd();
+
+    let s = u8x16::splat(b's');
+    let p = u8x16::splat(b'p');
+
+    let mut num_ss = 0;
+    let mut num_ps = 0;
+    for &window in middle {
+        let neg_ss = window.simd_eq(s).to_int();
+        let neg_ps = window.simd_eq(p).to_int();
+
+        num_ss += (-neg_ss.reduce_sum()) as u64;
+        num_ps += (-neg_ps.reduce_sum()) as u64;
+    }
+
+    let mut tally = _tally_scalar(prefix) + _tally_scalar(suffix);
+    tally.s += num_ss;
+    tally.p += num_ps;
+    tally.other = bytes.len() as u64 - tally.s - tally.p;
+    tally
+}
+
+/// Counts 16 bytes at a time. portable_simd needs nightly Rust, so without the
+/// `nightly-simd` feature, this uses SSE2 intrinsics on x86 and x86-64, and a plain
+/// loop everywhere else.
+#[cfg(not(feature = "nightly-simd"))]
+pub fn rust_portable_simd(bytes: &[u8]) -> i64 {
+    rust_portable_simd_tally(bytes).balance()
+}
+
+/// Like [rust_portable_simd], but returns the full [Tally].
+#[cfg(not(feature = "nightly-simd"))]
+pub fn rust_portable_simd_tally(bytes: &[u8]) -> Tally {
+    _tally_fallback(bytes)
+}
+
+#[cfg(all(
+    not(feature = "nightly-simd"),
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+#[inline(always)]
+fn _tally_fallback(bytes: &[u8]) -> Tally {
+    // Safety: we're compiled for CPUs that support SSE2.
+    unsafe { super::x86_sse2::tally_sse2(bytes) }
+}
+
+#[cfg(all(
+    not(feature = "nightly-simd"),
+    not(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse2"
+    ))
+))]
+#[inline(always)]
+fn _tally_fallback(bytes: &[u8]) -> Tally {
+    let mut tally = _tally_scalar(bytes);
+    tally.other = bytes.len() as u64 - tally.s - tally.p;
+    tally
+}
+
 // Basically the for-loop version, but takes a slice.
+#[cfg_attr(
+    all(
+        not(feature = "nightly-simd"),
+        not(any(target_arch = "x86", target_arch = "x86_64"))
+    ),
+    allow(dead_code)
+)]
 #[inline(always)]
-fn _count_scalar(s: &[u8]) -> isize {
+pub(super) fn _count_scalar(s: &[u8]) -> i64 {
     let mut result = 0;
     for &c in s {
         if c == b's' {
@@ -52,3 +125,18 @@ fn _count_scalar(s: &[u8]) -> isize {
 
     result
 }
+
+// Like _count_scalar, but keeps the counts separate. Does not count "other" bytes.
+#[inline(always)]
+pub(super) fn _tally_scalar(s: &[u8]) -> Tally {
+    let mut tally = Tally::default();
+    for &c in s {
+        if c == b's' {
+            tally.s += 1;
+        } else if c == b'p' {
+            tally.p += 1;
+        }
+    }
+
+    tally
+}
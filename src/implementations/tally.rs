@@ -0,0 +1,61 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::ops::{Add, AddAssign};
+
+/// How many of each kind of byte were in the input.
+///
+/// This has the same layout as `struct tally` in c/tally.h.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Tally {
+    /// Number of 's' bytes.
+    pub s: u64,
+    /// Number of 'p' bytes.
+    pub p: u64,
+    /// Number of bytes that are neither 's' nor 'p'.
+    pub other: u64,
+}
+
+impl Tally {
+    /// The number of 's' minus the number of 'p'. This is what the rest of the
+    /// implementations return.
+    pub fn balance(&self) -> i64 {
+        self.s as i64 - self.p as i64
+    }
+
+    /// The total number of bytes counted.
+    pub fn total(&self) -> u64 {
+        self.s + self.p + self.other
+    }
+}
+
+impl Add for Tally {
+    type Output = Tally;
+
+    fn add(self, rhs: Tally) -> Tally {
+        Tally {
+            s: self.s + rhs.s,
+            p: self.p + rhs.p,
+            other: self.other + rhs.other,
+        }
+    }
+}
+
+impl AddAssign for Tally {
+    fn add_assign(&mut self, rhs: Tally) {
+        *self = *self + rhs;
+    }
+}
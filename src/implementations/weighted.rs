@@ -0,0 +1,208 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implementations that sum an arbitrary weight per byte, instead of hard-coding
+//! 's' => +1 and 'p' => -1.
+//!
+//! The s/p problem is the [WeightTable::SP] preset: for example,
+//! `rust_weighted_lookup(bytes, &WeightTable::SP)` is another way to write
+//! `rust_for_loop(bytes)`. The s/p implementations themselves don't go through a
+//! table, though. They're what the benchmarks compare, and the point of comparing
+//! them is how the compiler treats two constants baked into each loop; loading the
+//! weights from a table at runtime would measure something else.
+
+#[cfg(feature = "nightly-simd")]
+use std::simd::{i8x16, u8x16, SimdInt, SimdPartialEq};
+
+/// A signed weight for every possible byte value, like the `categorize[256]` table
+/// in c/count-machine.c.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeightTable([i8; 256]);
+
+impl WeightTable {
+    /// The problem from the blog post: 's' => +1, 'p' => -1, and everything else is 0.
+    /// Counting with this table gives the same result as every s/p implementation.
+    pub const SP: WeightTable = WeightTable::zeros().with(b's', 1).with(b'p', -1);
+
+    /// A table where every byte has a weight of zero.
+    pub const fn zeros() -> Self {
+        WeightTable([0; 256])
+    }
+
+    /// A table with the given weight for each byte value.
+    pub const fn from_array(weights: [i8; 256]) -> Self {
+        WeightTable(weights)
+    }
+
+    /// Returns a copy of this table, where `byte` has the given `weight`.
+    pub const fn with(mut self, byte: u8, weight: i8) -> Self {
+        self.0[byte as usize] = weight;
+        self
+    }
+
+    /// The weight of `byte`.
+    #[inline(always)]
+    pub fn weight(&self, byte: u8) -> i8 {
+        self.0[byte as usize]
+    }
+
+    /// Every byte that has a nonzero weight, along with its weight.
+    pub fn nonzero_weights(&self) -> Vec<(u8, i8)> {
+        (0..=u8::MAX)
+            .map(|byte| (byte, self.weight(byte)))
+            .filter(|&(_, weight)| weight != 0)
+            .collect()
+    }
+}
+
+impl Default for WeightTable {
+    fn default() -> Self {
+        WeightTable::zeros()
+    }
+}
+
+/// Sums weights by comparing each byte against every byte with a nonzero weight,
+/// like [rust_for_loop](super::rust_for_loop)'s chain of if-statements.
+pub fn rust_weighted_scalar(bytes: &[u8], table: &WeightTable) -> i64 {
+    let weights = table.nonzero_weights();
+
+    let mut result = 0;
+    for &c in bytes {
+        for &(byte, weight) in &weights {
+            if c == byte {
+                result += weight as i64;
+                break;
+            }
+        }
+    }
+
+    result
+}
+
+/// Sums weights by looking up each byte in the table.
+pub fn rust_weighted_lookup(bytes: &[u8], table: &WeightTable) -> i64 {
+    bytes.iter().map(|&c| table.weight(c) as i64).sum()
+}
+
+/// Sums weights using portable_simd. Each window is compared against every byte
+/// with a nonzero weight, so this is fastest when few bytes have a nonzero weight.
+#[cfg(feature = "nightly-simd")]
+pub fn rust_weighted_portable_simd(bytes: &[u8], table: &WeightTable) -> i64 {
+    let (prefix, middle, suffix) = bytes.as_simd();
+
+    let weights: Vec<_> = table
+        .nonzero_weights()
+        .into_iter()
+        .map(|(byte, weight)| (u8x16::splat(byte), i8x16::splat(weight)))
+        .collect();
+
+    let mut result = 0;
+    for &window in middle {
+        // Each byte matches at most one entry, so every lane holds a single weight.
+        let mut lanes = i8x16::splat(0);
+        for &(byte, weight) in &weights {
+            lanes = window.simd_eq(byte).select(weight, lanes);
+        }
+
+        // Widen before summing, so that 16 large weights can't overflow.
+        result += lanes.cast::<i16>().reduce_sum() as i64;
+    }
+
+    rust_weighted_lookup(prefix, table) + result + rust_weighted_lookup(suffix, table)
+}
+
+/// Sums weights 16 bytes at a time. portable_simd needs nightly Rust, so without
+/// the `nightly-simd` feature, this uses SSE2 intrinsics on x86 and x86-64, and
+/// [rust_weighted_lookup] everywhere else.
+#[cfg(not(feature = "nightly-simd"))]
+pub fn rust_weighted_portable_simd(bytes: &[u8], table: &WeightTable) -> i64 {
+    _weighted_fallback(bytes, table)
+}
+
+#[cfg(all(
+    not(feature = "nightly-simd"),
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+#[inline(always)]
+fn _weighted_fallback(bytes: &[u8], table: &WeightTable) -> i64 {
+    // Safety: we're compiled for CPUs that support SSE2.
+    unsafe { super::x86_sse2::weighted_sse2(bytes, table) }
+}
+
+#[cfg(all(
+    not(feature = "nightly-simd"),
+    not(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse2"
+    ))
+))]
+#[inline(always)]
+fn _weighted_fallback(bytes: &[u8], table: &WeightTable) -> i64 {
+    rust_weighted_lookup(bytes, table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+
+    macro_rules! test_weighted {
+        ($implementation: ident) => {
+            mod $implementation {
+                use super::*;
+
+                #[test]
+                #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+                fn test_sp_preset_matches_count() {
+                    for (bytes, manifest) in data::with_manifests() {
+                        assert_eq!(manifest.balance, $implementation(bytes, &WeightTable::SP));
+                    }
+                }
+
+                #[test]
+                fn test_arbitrary_table() {
+                    // Every byte gets a different weight, including the extremes, to
+                    // make sure that the SIMD implementation doesn't overflow.
+                    let mut weights = [0; 256];
+                    for (byte, weight) in weights.iter_mut().enumerate() {
+                        *weight = (byte as u8).wrapping_mul(7) as i8;
+                    }
+                    let table = WeightTable::from_array(weights)
+                        .with(b'a', i8::MAX)
+                        .with(b'b', i8::MIN);
+
+                    let mut bytes: Vec<u8> = (0..=u8::MAX).cycle().take(4099).collect();
+                    bytes.extend([b'a'; 100]);
+                    bytes.extend([b'b'; 37]);
+                    let expected: i64 = bytes.iter().map(|&c| table.weight(c) as i64).sum();
+
+                    assert_eq!(expected, $implementation(&bytes, &table));
+                }
+
+                #[test]
+                #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+                fn test_empty_table() {
+                    let bytes = data::RANDOM_PRINTABLE.as_bytes();
+                    assert_eq!(0, $implementation(bytes, &WeightTable::zeros()));
+                }
+            }
+        };
+    }
+
+    test_weighted!(rust_weighted_scalar);
+    test_weighted!(rust_weighted_lookup);
+    test_weighted!(rust_weighted_portable_simd);
+}
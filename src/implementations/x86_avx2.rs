@@ -0,0 +1,114 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use super::rust_portable_simd::_count_scalar;
+use super::x86_sad::{count_windows, Lanes};
+
+/// Counts using hand-written AVX2 intrinsics.
+///
+/// Each 32-byte window is compared against 's' and 'p', and the matches are
+/// counted in 8-bit lanes. Every 255 windows (before the lanes can overflow), the
+/// lanes are summed into 64-bit lanes with `_mm256_sad_epu8`.
+///
+/// # Panics
+///
+/// Panics if this CPU doesn't support AVX2.
+pub fn rust_x86_avx2(bytes: &[u8]) -> i64 {
+    assert!(
+        is_x86_feature_detected!("avx2"),
+        "rust_x86_avx2 requires a CPU with AVX2"
+    );
+
+    // Safety: we just checked that the CPU supports AVX2.
+    unsafe { count_avx2(bytes) }
+}
+
+#[target_feature(enable = "avx2")]
+pub(super) unsafe fn count_avx2(bytes: &[u8]) -> i64 {
+    let (num_ss, num_ps, remainder) = count_windows::<__m256i>(bytes);
+    num_ss as i64 - num_ps as i64 + _count_scalar(remainder)
+}
+
+impl Lanes for __m256i {
+    const WIDTH: usize = 32;
+
+    #[inline(always)]
+    unsafe fn zero() -> Self {
+        _mm256_setzero_si256()
+    }
+
+    #[inline(always)]
+    unsafe fn splat(byte: u8) -> Self {
+        _mm256_set1_epi8(byte as i8)
+    }
+
+    #[inline(always)]
+    unsafe fn load(window: &[u8]) -> Self {
+        debug_assert!(window.len() >= Self::WIDTH);
+        _mm256_loadu_si256(window.as_ptr() as *const __m256i)
+    }
+
+    #[inline(always)]
+    unsafe fn count_eq(self, a: Self, b: Self) -> Self {
+        // Matches are -1, so subtracting them adds one to the lane's count.
+        _mm256_sub_epi8(self, _mm256_cmpeq_epi8(a, b))
+    }
+
+    #[inline(always)]
+    unsafe fn add_sad(self, counts: Self) -> Self {
+        _mm256_add_epi64(self, _mm256_sad_epu8(counts, _mm256_setzero_si256()))
+    }
+
+    #[inline(always)]
+    unsafe fn sum_u64(self) -> u64 {
+        let lanes: [u64; 4] = std::mem::transmute(self);
+        lanes.iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_boundaries() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+
+        // 255 windows of 32 bytes fill up the 8-bit counters exactly.
+        let all_s = vec![b's'; 3 * 255 * 32 + 100];
+        let all_p = vec![b'p'; 3 * 255 * 32 + 100];
+        for len in [
+            0,
+            31,
+            32,
+            33,
+            254 * 32,
+            255 * 32,
+            255 * 32 + 1,
+            256 * 32,
+            all_s.len(),
+        ] {
+            assert_eq!(len as i64, rust_x86_avx2(&all_s[..len]), "{len}");
+            assert_eq!(-(len as i64), rust_x86_avx2(&all_p[..len]), "{len}");
+        }
+    }
+}
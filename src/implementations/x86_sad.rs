@@ -0,0 +1,78 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The counting loop shared by the hand-written SSE2 and AVX2 implementations.
+//!
+//! Matches are counted in 8-bit lanes, which would overflow after 255 windows, so
+//! windows are counted in batches. After each batch, `psadbw` (`_mm_sad_epu8` or
+//! `_mm256_sad_epu8`) sums each group of eight 8-bit lanes into a 64-bit lane.
+
+/// A SIMD register of 8-bit lanes, as used by [count_windows].
+///
+/// The methods call intrinsics that need a target feature (e.g., AVX2), so they're
+/// `unsafe`, and must be inlined into a `#[target_feature]` function to be compiled
+/// with its instructions.
+pub(super) trait Lanes: Copy {
+    /// The number of bytes in each window.
+    const WIDTH: usize;
+
+    unsafe fn zero() -> Self;
+    unsafe fn splat(byte: u8) -> Self;
+    /// Loads the first [Self::WIDTH] bytes of `window`.
+    unsafe fn load(window: &[u8]) -> Self;
+    /// Adds one to each 8-bit lane of `self` where `a` and `b` are equal.
+    unsafe fn count_eq(self, a: Self, b: Self) -> Self;
+    /// Sums each group of eight 8-bit lanes of `counts`, and adds them to the
+    /// 64-bit lanes of `self`.
+    unsafe fn add_sad(self, counts: Self) -> Self;
+    /// The sum of the 64-bit lanes.
+    unsafe fn sum_u64(self) -> u64;
+}
+
+/// The most windows we can count before an 8-bit lane counter could overflow.
+const MAX_WINDOWS_PER_BATCH: usize = u8::MAX as usize;
+
+/// Counts the 's' and 'p' bytes in every full window of `bytes`, [Lanes::WIDTH]
+/// bytes at a time. Returns the number of 's', the number of 'p', and the bytes
+/// left over after the last full window.
+#[inline(always)]
+pub(super) unsafe fn count_windows<V: Lanes>(bytes: &[u8]) -> (u64, u64, &[u8]) {
+    let s = V::splat(b's');
+    let p = V::splat(b'p');
+
+    let mut windows = bytes.chunks_exact(V::WIDTH);
+    let mut total_ss = V::zero();
+    let mut total_ps = V::zero();
+    loop {
+        let mut ss = V::zero();
+        let mut ps = V::zero();
+        let mut n_windows = 0;
+        for window in windows.by_ref().take(MAX_WINDOWS_PER_BATCH) {
+            let window = V::load(window);
+            ss = ss.count_eq(window, s);
+            ps = ps.count_eq(window, p);
+            n_windows += 1;
+        }
+
+        if n_windows == 0 {
+            break;
+        }
+
+        total_ss = total_ss.add_sad(ss);
+        total_ps = total_ps.add_sad(ps);
+    }
+
+    (total_ss.sum_u64(), total_ps.sum_u64(), windows.remainder())
+}
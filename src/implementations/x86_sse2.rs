@@ -0,0 +1,115 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! SSE2 intrinsics, for when portable_simd isn't available (i.e., on stable Rust,
+//! without the `nightly-simd` feature).
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use super::rust_portable_simd::_tally_scalar;
+use super::x86_sad::{count_windows, Lanes};
+use super::{rust_weighted_lookup, Tally, WeightTable};
+
+/// Counts using SSE2 intrinsics. See [tally_sse2].
+#[target_feature(enable = "sse2")]
+pub(super) unsafe fn count_sse2(bytes: &[u8]) -> i64 {
+    tally_sse2(bytes).balance()
+}
+
+/// Tallies using SSE2 intrinsics, 16 bytes at a time. Like
+/// [rust_x86_avx2](super::rust_x86_avx2), matches are counted in 8-bit lanes, which
+/// are summed with `_mm_sad_epu8` every 255 windows.
+#[target_feature(enable = "sse2")]
+pub(super) unsafe fn tally_sse2(bytes: &[u8]) -> Tally {
+    let (num_ss, num_ps, remainder) = count_windows::<__m128i>(bytes);
+
+    let mut tally = _tally_scalar(remainder);
+    tally.s += num_ss;
+    tally.p += num_ps;
+    tally.other = bytes.len() as u64 - tally.s - tally.p;
+    tally
+}
+
+/// Sums weights using SSE2 intrinsics, 16 bytes at a time, like
+/// [rust_weighted_portable_simd](super::rust_weighted_portable_simd).
+#[target_feature(enable = "sse2")]
+pub(super) unsafe fn weighted_sse2(bytes: &[u8], table: &WeightTable) -> i64 {
+    let weights: Vec<_> = table
+        .nonzero_weights()
+        .into_iter()
+        .map(|(byte, weight)| (_mm_set1_epi8(byte as i8), _mm_set1_epi8(weight)))
+        .collect();
+    let sign_bit = _mm_set1_epi8(i8::MIN);
+
+    let mut windows = bytes.chunks_exact(16);
+    let mut total = __m128i::zero();
+    for window in windows.by_ref() {
+        let window = __m128i::load(window);
+
+        // Each byte matches at most one entry, so every lane holds a single weight.
+        let mut lanes = __m128i::zero();
+        for &(byte, weight) in &weights {
+            lanes = _mm_or_si128(lanes, _mm_and_si128(_mm_cmpeq_epi8(window, byte), weight));
+        }
+
+        // _mm_sad_epu8 sums unsigned bytes, so flipping the sign bit adds 128 to
+        // every weight. That's taken back out below.
+        total = total.add_sad(_mm_xor_si128(lanes, sign_bit));
+    }
+
+    let n_windows = (bytes.len() / 16) as i64;
+    let result = total.sum_u64() as i64 - n_windows * 16 * 128;
+    result + rust_weighted_lookup(windows.remainder(), table)
+}
+
+impl Lanes for __m128i {
+    const WIDTH: usize = 16;
+
+    #[inline(always)]
+    unsafe fn zero() -> Self {
+        _mm_setzero_si128()
+    }
+
+    #[inline(always)]
+    unsafe fn splat(byte: u8) -> Self {
+        _mm_set1_epi8(byte as i8)
+    }
+
+    #[inline(always)]
+    unsafe fn load(window: &[u8]) -> Self {
+        debug_assert!(window.len() >= Self::WIDTH);
+        _mm_loadu_si128(window.as_ptr() as *const __m128i)
+    }
+
+    #[inline(always)]
+    unsafe fn count_eq(self, a: Self, b: Self) -> Self {
+        // Matches are -1, so subtracting them adds one to the lane's count.
+        _mm_sub_epi8(self, _mm_cmpeq_epi8(a, b))
+    }
+
+    #[inline(always)]
+    unsafe fn add_sad(self, counts: Self) -> Self {
+        _mm_add_epi64(self, _mm_sad_epu8(counts, _mm_setzero_si128()))
+    }
+
+    #[inline(always)]
+    unsafe fn sum_u64(self) -> u64 {
+        let lanes: [u64; 2] = std::mem::transmute(self);
+        lanes.iter().sum()
+    }
+}
@@ -13,195 +13,286 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-#![feature(portable_simd)]
-#![feature(test)]
-
-extern crate test;
+#![cfg_attr(feature = "nightly-simd", feature(portable_simd))]
 
 pub mod data;
+pub mod file;
+// Only public for the fuzz targets in fuzz/.
+#[doc(hidden)]
+pub mod fuzzing;
 pub mod implementations;
+pub mod parallel;
+pub mod rng;
+pub mod stream;
 
 pub use implementations::*;
 
+/// Counts the number of 's' minus the number of 'p' in `bytes`, as fast as this CPU
+/// can. If you don't care which implementation you get, use this one.
+#[inline]
+pub fn count(bytes: &[u8]) -> i64 {
+    rust_dispatch(bytes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use std::ffi::CString;
+    use std::collections::HashSet;
+    use std::ffi::{CStr, CString};
 
-    macro_rules! test_implementation {
-        ($implementation: ident) => {
-            mod $implementation {
-                use std::ffi::CString;
+    mod differential;
 
-                #[test]
-                fn test_small_sentence() {
-                    let sentence = CString::new("sspspss").unwrap();
-                    assert_eq!(
-                        3,
-                        crate::implementations::$implementation(sentence.as_c_str())
-                    );
-                }
+    /// The implementations to test. Miri can't call into the C library, so under
+    /// Miri, only the implementations written in Rust are tested.
+    pub(crate) fn testable() -> impl Iterator<Item = &'static dyn Counter> {
+        supported().filter(|counter| !cfg!(miri) || counter.language() == Language::Rust)
+    }
 
-                #[test]
-                fn test_big_sentence() {
-                    let sentence = CString::new("ssssspssspssspp.pssspspppsppppsp").unwrap();
-                    assert_eq!(
-                        3,
-                        crate::implementations::$implementation(sentence.as_c_str())
-                    );
-                }
-            }
-        };
+    #[test]
+    fn test_small_sentence() {
+        let sentence = CString::new("sspspss").unwrap();
+        for counter in testable() {
+            assert_eq!(3, counter.count(&sentence), "{}", counter.name());
+        }
+    }
+
+    #[test]
+    fn test_big_sentence() {
+        let sentence = CString::new("ssssspssspssspp.pssspspppsppppsp").unwrap();
+        for counter in testable() {
+            assert_eq!(3, counter.count(&sentence), "{}", counter.name());
+        }
     }
 
-    test_implementation!(c_original);
-    test_implementation!(c_for_loop);
-    test_implementation!(c_while_loop);
-    test_implementation!(rust_emulate_numpy);
-    test_implementation!(rust_for_loop);
-    test_implementation!(rust_iter);
-    test_implementation!(rust_portable_simd);
-    test_implementation!(c_state_machine);
-    test_implementation!(c_count_machine);
+    /// Checks every implementation against the answer that build.rs recorded when it
+    /// generated the dataset.
+    fn assert_matches_manifest(dataset: &str, manifest: data::Manifest) {
+        assert_eq!(
+            manifest.size,
+            dataset.len() as u64,
+            "seed {}",
+            manifest.seed
+        );
+        assert_eq!(
+            manifest.checksum,
+            data::fnv1a64(dataset.as_bytes()),
+            "seed {}",
+            manifest.seed
+        );
+
+        let sentence = CString::new(dataset).unwrap();
+        for counter in testable() {
+            assert_eq!(
+                manifest.balance,
+                counter.count(&sentence),
+                "{} (seed {})",
+                counter.name(),
+                manifest.seed
+            );
+        }
+    }
 
     #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
     fn test_implementations_have_identical_results_only_sp() {
-        let buffer = CString::new(data::RANDOM_SP).unwrap();
-        let sentence = buffer.as_c_str();
-        let count_from_iter = rust_for_loop(sentence);
-
-        assert_eq!(count_from_iter, rust_iter(sentence));
-        assert_eq!(count_from_iter, rust_portable_simd(sentence));
-        assert_eq!(count_from_iter, c_while_loop(sentence));
-        assert_eq!(count_from_iter, rust_emulate_numpy(sentence));
-        assert_eq!(count_from_iter, c_state_machine(sentence));
-        assert_eq!(count_from_iter, c_count_machine(sentence));
+        assert_matches_manifest(data::RANDOM_SP, data::random_sp_manifest());
     }
 
     #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
     fn test_implementations_have_identical_results_any_printable() {
-        let sentence = CString::new(data::RANDOM_PRINTABLE).unwrap();
-        let sentence = sentence.as_c_str();
-        let count_from_iter = rust_for_loop(sentence);
-
-        assert_eq!(count_from_iter, rust_iter(sentence));
-        assert_eq!(count_from_iter, rust_portable_simd(sentence));
-        assert_eq!(count_from_iter, c_while_loop(sentence));
-        assert_eq!(count_from_iter, rust_emulate_numpy(sentence));
-        assert_eq!(count_from_iter, c_state_machine(sentence));
-        assert_eq!(count_from_iter, c_count_machine(sentence));
+        assert_matches_manifest(data::RANDOM_PRINTABLE, data::random_printable_manifest());
     }
-}
 
-#[cfg(test)]
-mod benches {
-    macro_rules! bench_implementation {
-        ($implementation: ident) => {
-            mod $implementation {
+    #[test]
+    fn test_count_bytes() {
+        let sentence = b"ssssspssspssspp.pssspspppsppppsp";
+        for counter in testable() {
+            assert_eq!(Ok(3), counter.count_bytes(sentence), "{}", counter.name());
+        }
+    }
 
-                use std::ffi::CString;
-                use test::Bencher;
+    #[test]
+    fn test_count_bytes_with_interior_nul() {
+        let sentence = b"sss\0pp";
+        for counter in testable() {
+            match counter.input() {
+                Input::NulTerminated => assert!(counter.count_bytes(sentence).is_err()),
+                Input::Sized => assert_eq!(Ok(1), counter.count_bytes(sentence)),
+            }
+        }
+    }
 
-                #[bench]
-                fn bench_random_sp(b: &mut Bencher) {
-                    let buffer = CString::new(crate::data::RANDOM_SP).unwrap();
-                    let sentence = test::black_box(buffer.as_c_str());
+    macro_rules! test_tally {
+        ($tally: ident, $count: ident) => {
+            mod $tally {
+                use crate::*;
 
-                    b.iter(|| crate::implementations::$implementation(sentence));
+                #[test]
+                fn test_big_sentence() {
+                    let sentence = b"ssssspssspssspp.pssspspppsppppsp";
+                    let expected = Tally {
+                        s: 17,
+                        p: 14,
+                        other: 1,
+                    };
+                    assert_eq!(expected, $tally(sentence));
                 }
 
-                #[bench]
-                fn bench_random_printable(b: &mut Bencher) {
-                    let buffer = CString::new(crate::data::RANDOM_PRINTABLE).unwrap();
-                    let sentence = test::black_box(buffer.as_c_str());
+                #[test]
+                #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+                fn test_balance_matches_count() {
+                    for dataset in [data::RANDOM_SP, data::RANDOM_PRINTABLE] {
+                        let bytes = dataset.as_bytes();
+                        let tally = $tally(bytes);
+                        assert_eq!($count(bytes), tally.balance());
+                        assert_eq!(bytes.len() as u64, tally.total());
+                    }
+                }
 
-                    b.iter(|| crate::implementations::$implementation(sentence));
+                #[test]
+                #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+                fn test_matches_manifest() {
+                    for (dataset, manifest) in [
+                        (data::RANDOM_SP, data::random_sp_manifest()),
+                        (data::RANDOM_PRINTABLE, data::random_printable_manifest()),
+                    ] {
+                        let tally = $tally(dataset.as_bytes());
+                        assert_eq!((manifest.s, manifest.p), (tally.s, tally.p));
+                    }
                 }
             }
         };
     }
 
-    macro_rules! bench_vec_eq_implementation {
-        ($implementation: ident) => {
-            mod $implementation {
-
-                use test::Bencher;
-
-                #[bench]
-                fn bench_random_sp(b: &mut Bencher) {
-                    let sentence = test::black_box(crate::data::RANDOM_SP.as_bytes());
-                    b.iter(|| crate::implementations::$implementation(sentence, b's'));
-                }
+    test_tally!(rust_emulate_numpy_tally, rust_emulate_numpy);
+    test_tally!(rust_portable_simd_tally, rust_portable_simd);
+    // Miri can't call the C implementations.
+    #[cfg(not(miri))]
+    test_tally!(c_state_machine_tally, c_state_machine);
+    #[cfg(not(miri))]
+    test_tally!(c_count_machine_tally, c_count_machine);
+
+    /// Small inputs for the pure-Rust implementations, so that their unsafe code can
+    /// be checked with `cargo miri test` in a reasonable amount of time.
+    mod miri {
+        use crate::*;
+
+        use std::mem::MaybeUninit;
+
+        /// Every length up to a few SIMD windows, at every offset within a window,
+        /// with bytes from a small alphabet that includes non-ASCII bytes.
+        fn small_inputs() -> impl Iterator<Item = Vec<u8>> {
+            const ALPHABET: &[u8] = b"sp.\0\x80\xff";
+            let mut state: u32 = 0x5eed;
+            let mut next_byte = move || {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                ALPHABET[(state >> 24) as usize % ALPHABET.len()]
+            };
+
+            (0..=70).map(move |len| (0..len).map(|_| next_byte()).collect())
+        }
 
-                #[bench]
-                fn bench_random_printable(b: &mut Bencher) {
-                    let sentence = test::black_box(crate::data::RANDOM_PRINTABLE.as_bytes());
-                    b.iter(|| crate::implementations::$implementation(sentence, b's'));
+        /// Calls `f` with every small input, at several alignments.
+        fn for_each_small_input(mut f: impl FnMut(&[u8])) {
+            for input in small_inputs() {
+                let mut buffer = vec![b'x'; 32];
+                buffer.extend(&input);
+                for offset in [0, 1, 7, 16, 31] {
+                    let start = 32 - offset;
+                    f(&buffer[start..start + input.len()]);
                 }
             }
-        };
-    }
+        }
 
-    bench_implementation!(c_original);
-    bench_implementation!(c_for_loop);
-    bench_implementation!(c_while_loop);
-    bench_implementation!(rust_emulate_numpy);
-    bench_implementation!(rust_for_loop);
-    bench_implementation!(rust_iter);
-    bench_implementation!(rust_portable_simd);
-    bench_implementation!(c_state_machine);
-    bench_implementation!(c_count_machine);
-
-    bench_vec_eq_implementation!(vec_eq);
-    bench_vec_eq_implementation!(vec_eq_simd);
-    bench_vec_eq_implementation!(vec_eq_do_nothing_but_allocate);
-    bench_vec_eq_implementation!(vec_eq_only_prefix);
-
-    mod vec_eq_only_simd {
-        use crate::implementations::vec_eq_only_simd;
-        use test::Bencher;
-
-        #[bench]
-        fn bench_random_sp(b: &mut Bencher) {
-            let input = crate::data::RANDOM_SP.as_bytes();
-            let value = b's';
-
-            const N: usize = 32;
-            let mut buffer = Vec::<bool>::with_capacity(input.len());
-
-            let n_initial_bytes = input.len() % N;
-            unsafe {
-                // Pretend the buffer is large enough. UB be here:
-                buffer.set_len(input.len());
-            }
+        #[test]
+        fn test_rust_implementations() {
+            for_each_small_input(|bytes| {
+                let expected = rust_for_loop(bytes);
+                assert_eq!(expected, rust_emulate_numpy(bytes));
+                assert_eq!(expected, rust_portable_simd(bytes));
+                assert_eq!(expected, rust_iter(bytes));
+                assert_eq!(expected, rust_dispatch(bytes));
+                assert_eq!(
+                    expected,
+                    rust_weighted_portable_simd(bytes, &WeightTable::SP)
+                );
+            });
+        }
 
-            b.iter(|| {
-                vec_eq_only_simd(
-                    test::black_box(&input[n_initial_bytes..]),
-                    &mut buffer[n_initial_bytes..],
-                    value,
-                )
+        #[test]
+        fn test_tallies() {
+            for_each_small_input(|bytes| {
+                assert_eq!(
+                    rust_emulate_numpy_tally(bytes),
+                    rust_portable_simd_tally(bytes)
+                );
+                assert_eq!(
+                    rust_for_loop(bytes),
+                    rust_portable_simd_tally(bytes).balance()
+                );
             });
         }
-    }
 
-    mod nonzero {
-        use crate::implementations::{nonzeros, vec_eq};
-        use test::Bencher;
+        #[test]
+        fn test_vec_eq_sound() {
+            for_each_small_input(|bytes| {
+                assert_eq!(vec_eq(bytes, b's'), vec_eq_simd_sound(bytes, b's'));
 
-        #[bench]
-        fn bench_random_sp(b: &mut Bencher) {
-            let vec = vec_eq(crate::data::RANDOM_SP.as_bytes(), b's');
-            let slice = test::black_box(&vec);
-            b.iter(|| nonzeros(slice));
+                let whole_windows = &bytes[..bytes.len() - bytes.len() % 32];
+                let mut buffer = vec![MaybeUninit::uninit(); whole_windows.len()];
+                vec_eq_only_simd_sound(whole_windows, &mut buffer, b'p');
+                let buffer: Vec<bool> = buffer.iter().map(|b| unsafe { b.assume_init() }).collect();
+                assert_eq!(vec_eq(whole_windows, b'p'), buffer);
+            });
         }
+    }
 
-        #[bench]
-        fn bench_random_printable(b: &mut Bencher) {
-            let vec = vec_eq(crate::data::RANDOM_PRINTABLE.as_bytes(), b's');
-            let slice = test::black_box(&vec);
-            b.iter(|| nonzeros(slice));
+    /// Asserts that every implementation counts `len` 's' bytes correctly. Inputs this
+    /// large take several GiB of memory, so the tests that use this are ignored by
+    /// default. CI runs them one at a time with
+    /// `cargo test --release --lib -- --ignored larger_than --test-threads=1`.
+    fn assert_counts_large_input(len: usize) {
+        let mut buffer = vec![b's'; len];
+        buffer.push(b'\0');
+        let sentence = CStr::from_bytes_with_nul(&buffer).unwrap();
+
+        for counter in testable() {
+            // It needs two more input-sized buffers, which is too much past 4 GiB.
+            if counter.name() == "rust_emulate_numpy" && len > u32::MAX as usize {
+                continue;
+            }
+            assert_eq!(len as i64, counter.count(sentence), "{}", counter.name());
         }
     }
+
+    #[test]
+    #[ignore = "allocates more than 2 GiB"]
+    fn test_result_larger_than_i32() {
+        assert_counts_large_input(i32::MAX as usize + 17);
+    }
+
+    #[test]
+    #[ignore = "allocates more than 4 GiB"]
+    fn test_result_larger_than_u32() {
+        assert_counts_large_input(u32::MAX as usize + 17);
+    }
+
+    #[test]
+    fn test_registry_names_are_unique() {
+        let names: HashSet<_> = IMPLEMENTATIONS.iter().map(|c| c.name()).collect();
+        assert_eq!(IMPLEMENTATIONS.len(), names.len());
+    }
+
+    #[test]
+    fn test_find() {
+        let counter = find("rust_portable_simd").unwrap();
+        assert_eq!("rust_portable_simd", counter.name());
+        assert_eq!(Language::Rust, counter.language());
+        assert_eq!(Input::Sized, counter.input());
+
+        assert_eq!(Input::NulTerminated, find("c_original").unwrap().input());
+        assert!(find("python_numpy").is_none());
+    }
 }
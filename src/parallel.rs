@@ -0,0 +1,130 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Counting on multiple threads, so that we're not limited by the memory
+//! bandwidth of a single core.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::implementations::rust_portable_simd;
+
+/// How many bytes each thread counts at a time, by default. Small enough to fit
+/// in L2 cache, and big enough that threads rarely contend for the next chunk.
+pub const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The number of threads to use when the caller doesn't have an opinion: one per
+/// CPU, or just one if we can't tell how many CPUs there are.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Counts `bytes` on `threads` threads, [DEFAULT_CHUNK_SIZE] bytes at a time.
+///
+/// # Panics
+///
+/// Panics if `threads` is zero.
+pub fn count_parallel(bytes: &[u8], threads: usize) -> i64 {
+    count_parallel_with_chunk_size(bytes, threads, DEFAULT_CHUNK_SIZE)
+}
+
+/// Counts `bytes` on `threads` threads, `chunk_size` bytes at a time.
+///
+/// Threads take the next uncounted chunk as soon as they're done with their last
+/// one, so a thread that gets descheduled doesn't hold everyone else up.
+///
+/// # Panics
+///
+/// Panics if `threads` or `chunk_size` is zero.
+pub fn count_parallel_with_chunk_size(bytes: &[u8], threads: usize, chunk_size: usize) -> i64 {
+    assert!(threads > 0, "need at least one thread");
+    assert!(chunk_size > 0, "chunk size must be nonzero");
+
+    let n_chunks = bytes.len().div_ceil(chunk_size);
+    let threads = threads.min(n_chunks);
+    if threads <= 1 {
+        return rust_portable_simd(bytes);
+    }
+
+    let next_chunk = AtomicUsize::new(0);
+    let worker = || {
+        let mut result = 0;
+        loop {
+            let i = next_chunk.fetch_add(1, Ordering::Relaxed);
+            if i >= n_chunks {
+                return result;
+            }
+
+            let start = i * chunk_size;
+            let end = bytes.len().min(start + chunk_size);
+            result += rust_portable_simd(&bytes[start..end]);
+        }
+    };
+
+    thread::scope(|scope| {
+        // The current thread is one of the workers, so spawn one fewer thread.
+        let handles: Vec<_> = (1..threads).map(|_| scope.spawn(worker)).collect();
+        let result = worker();
+
+        result
+            + handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .sum::<i64>()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data, rust_for_loop};
+
+    #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+    fn test_count_parallel() {
+        for (bytes, manifest) in data::with_manifests() {
+            let expected = manifest.balance;
+
+            for threads in [1, 2, 3, 8] {
+                assert_eq!(expected, count_parallel(bytes, threads), "{threads}");
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+    fn test_awkward_chunk_sizes() {
+        let bytes = &data::RANDOM_SP.as_bytes()[..100_003];
+        let expected = rust_for_loop(bytes);
+
+        for chunk_size in [1, 15, 16, 17, 4095, 100_002, 100_003, 1_000_000] {
+            for threads in [1, 2, 5] {
+                let actual = count_parallel_with_chunk_size(bytes, threads, chunk_size);
+                assert_eq!(expected, actual, "{threads} threads, {chunk_size} bytes");
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(0, count_parallel(b"", 4));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_threads() {
+        count_parallel(b"sp", 0);
+    }
+}
@@ -0,0 +1,174 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Counting input that doesn't fit in memory, such as large files and pipes.
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Read};
+
+use crate::implementations::{rust_portable_simd, rust_portable_simd_tally, Tally};
+
+/// How many bytes [count_reader] reads at a time. Big enough to amortize the cost
+/// of each read, small enough to stay in cache.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Counts everything in `reader`, [CHUNK_SIZE] bytes at a time.
+pub fn count_reader<R: Read>(reader: R) -> io::Result<i64> {
+    count_buf_reader(BufReader::with_capacity(CHUNK_SIZE, reader))
+}
+
+/// Tallies everything in `reader`, [CHUNK_SIZE] bytes at a time.
+pub fn tally_reader<R: Read>(reader: R) -> io::Result<Tally> {
+    let mut result = Tally::default();
+    for_each_chunk(BufReader::with_capacity(CHUNK_SIZE, reader), |chunk| {
+        result += rust_portable_simd_tally(chunk);
+        Ok::<_, io::Error>(())
+    })?;
+    Ok(result)
+}
+
+/// Counts everything in `reader`, one buffer at a time. Unlike [count_reader], this
+/// uses the reader's own buffer, whatever its size.
+pub fn count_buf_reader<R: BufRead>(reader: R) -> io::Result<i64> {
+    let mut result = 0;
+    for_each_chunk(reader, |chunk| {
+        result += rust_portable_simd(chunk);
+        Ok::<_, io::Error>(())
+    })?;
+    Ok(result)
+}
+
+/// Calls `f` on each buffer of `reader` until it runs out, or until `f` fails.
+/// Retries reads that were interrupted.
+pub(crate) fn for_each_chunk<R, E, F>(mut reader: R, mut f: F) -> Result<(), E>
+where
+    R: BufRead,
+    E: From<io::Error>,
+    F: FnMut(&[u8]) -> Result<(), E>,
+{
+    loop {
+        let buffer = match reader.fill_buf() {
+            Ok([]) => return Ok(()),
+            Ok(buffer) => buffer,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        f(buffer)?;
+
+        let n = buffer.len();
+        reader.consume(n);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+
+    /// A reader that returns short reads of awkward sizes, and is sometimes
+    /// interrupted, so that chunk boundaries land all over the place.
+    struct AwkwardReader<'a> {
+        bytes: &'a [u8],
+        read_sizes: std::iter::Cycle<std::slice::Iter<'static, usize>>,
+        calls: usize,
+    }
+
+    impl<'a> AwkwardReader<'a> {
+        fn new(bytes: &'a [u8]) -> Self {
+            AwkwardReader {
+                bytes,
+                read_sizes: [1, 15, 16, 17, 31, 33, 4095, 70_000].iter().cycle(),
+                calls: 0,
+            }
+        }
+    }
+
+    impl Read for AwkwardReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls == 5 {
+                self.calls = 0;
+                return Err(io::Error::from(ErrorKind::Interrupted));
+            }
+
+            let n = (*self.read_sizes.next().unwrap())
+                .min(buf.len())
+                .min(self.bytes.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    /// A reader that fails after the first read.
+    struct FailingReader(bool);
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0 {
+                return Err(io::Error::from(ErrorKind::BrokenPipe));
+            }
+            self.0 = true;
+            buf[0] = b's';
+            Ok(1)
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+    fn test_count_reader() {
+        for (bytes, manifest) in data::with_manifests() {
+            let expected = manifest.balance;
+
+            assert_eq!(expected, count_reader(bytes).unwrap());
+            assert_eq!(expected, count_reader(AwkwardReader::new(bytes)).unwrap());
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+    fn test_count_buf_reader() {
+        for (bytes, manifest) in data::with_manifests() {
+            let expected = manifest.balance;
+
+            assert_eq!(expected, count_buf_reader(bytes).unwrap());
+            for capacity in [1, 15, 16, 17, 4095, CHUNK_SIZE + 1] {
+                let reader = BufReader::with_capacity(capacity, AwkwardReader::new(bytes));
+                assert_eq!(expected, count_buf_reader(reader).unwrap(), "{capacity}");
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "uses the 12 MiB datasets")]
+    fn test_tally_reader() {
+        for (bytes, manifest) in data::with_manifests() {
+            let tally = tally_reader(AwkwardReader::new(bytes)).unwrap();
+            assert_eq!((manifest.s, manifest.p), (tally.s, tally.p));
+            assert_eq!(manifest.size, tally.total());
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(0, count_reader(io::empty()).unwrap());
+        assert_eq!(0, count_buf_reader(io::empty()).unwrap());
+    }
+
+    #[test]
+    fn test_errors_are_returned() {
+        assert!(count_reader(FailingReader(false)).is_err());
+        assert!(count_buf_reader(BufReader::new(FailingReader(false))).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright (C) 2023  Eddie Antonio Santos
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A randomized differential tester: generates inputs of random lengths, alphabets
+//! and alignments, checks that every implementation agrees with [rust_for_loop],
+//! and shrinks any input that makes them disagree into a minimal reproducer.
+//!
+//! The inputs come from a fixed seed by default. Set `FAST_SP_TEST_SEED` to try
+//! different ones, and `FAST_SP_TEST_CASES` to generate more (or fewer) inputs.
+
+use std::fmt;
+
+use crate::fuzzing::expected_and_actual;
+use crate::implementations::{rust_for_loop, Counter};
+use crate::rng::Rng;
+
+/// The widths that the SIMD implementations work in, in bytes. 255 * 32 is how
+/// many bytes rust_x86_avx2 counts before it has to empty its 8-bit counters.
+const SIMD_WIDTHS: [usize; 4] = [16, 32, 64, 255 * 32];
+
+/// An input, and where it starts in memory relative to a 64-byte boundary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Case {
+    bytes: Vec<u8>,
+    offset: usize,
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "b\"{}\" ({} bytes, offset {})",
+            self.bytes.escape_ascii(),
+            self.bytes.len(),
+            self.offset
+        )
+    }
+}
+
+impl Case {
+    fn generate(rng: &mut Rng) -> Case {
+        let len = match rng.below(3) {
+            // Just before, on, or just after a multiple of a SIMD width:
+            0 => {
+                let width = *rng.choose(&SIMD_WIDTHS);
+                let multiple = width * rng.below(4) as usize;
+                (multiple + rng.below(3) as usize).saturating_sub(1)
+            }
+            1 => rng.below(64) as usize,
+            _ => rng.below(10_000) as usize,
+        };
+
+        let bytes = match rng.below(5) {
+            0 => random_bytes(rng, len, b"sp"),
+            1 => random_bytes(rng, len, b"sp. "),
+            // 0xf3 and 0xf0 are 's' and 'p' with the high bit set, in case anything
+            // ignores the high bit, or sign-extends a plain char.
+            2 => random_bytes(rng, len, b"sp\x80\xff\xf3\xf0"),
+            // Mostly one letter, so runs are long:
+            3 => {
+                let common = *rng.choose(b"sp");
+                (0..len)
+                    .map(|_| if rng.chance(0.95) { common } else { b'.' })
+                    .collect()
+            }
+            // Anything, including NUL:
+            _ => (0..len).map(|_| rng.next_u64() as u8).collect(),
+        };
+
+        Case {
+            bytes,
+            offset: rng.below(64) as usize,
+        }
+    }
+
+    /// Returns the expected and actual results from `counter`, if they differ.
+    fn check(&self, counter: &dyn Counter) -> Option<(i64, i64)> {
+        let (expected, actual) = expected_and_actual(counter, &self.bytes, self.offset);
+        (expected != actual).then_some((expected, actual))
+    }
+
+    /// Makes the case as small and as simple as possible, while `counter` still
+    /// gets the wrong answer for it.
+    fn shrink(mut self, counter: &dyn Counter) -> Case {
+        let fails = |case: &Case| case.check(counter).is_some();
+        debug_assert!(fails(&self));
+
+        loop {
+            let before = self.clone();
+
+            // Try aligning it:
+            for offset in 0..self.offset {
+                let candidate = Case {
+                    offset,
+                    ..self.clone()
+                };
+                if fails(&candidate) {
+                    self = candidate;
+                    break;
+                }
+            }
+
+            // Try removing chunks, from large to small:
+            let mut chunk_size = self.bytes.len() / 2;
+            while chunk_size > 0 {
+                let mut start = 0;
+                while start < self.bytes.len() {
+                    let mut candidate = self.clone();
+                    let end = self.bytes.len().min(start + chunk_size);
+                    candidate.bytes.drain(start..end);
+                    if fails(&candidate) {
+                        self = candidate;
+                    } else {
+                        start += chunk_size;
+                    }
+                }
+                chunk_size /= 2;
+            }
+
+            // Try replacing bytes with something boring:
+            for i in 0..self.bytes.len() {
+                if self.bytes[i] != b'.' {
+                    let mut candidate = self.clone();
+                    candidate.bytes[i] = b'.';
+                    if fails(&candidate) {
+                        self = candidate;
+                    }
+                }
+            }
+
+            if self == before {
+                return self;
+            }
+        }
+    }
+}
+
+fn random_bytes(rng: &mut Rng, len: usize, alphabet: &[u8]) -> Vec<u8> {
+    (0..len).map(|_| *rng.choose(alphabet)).collect()
+}
+
+/// Checks `n_cases` random inputs on every one of `counters`. Panics with a minimal
+/// reproducer if any of them disagree with [rust_for_loop].
+fn assert_all_agree<'a>(
+    counters: impl Iterator<Item = &'a dyn Counter>,
+    seed: u64,
+    n_cases: usize,
+) {
+    let counters: Vec<_> = counters.collect();
+    let mut rng = Rng::new(seed);
+
+    for i in 0..n_cases {
+        let case = Case::generate(&mut rng);
+        for &counter in &counters {
+            if case.check(counter).is_some() {
+                let case = case.shrink(counter);
+                let (expected, actual) = case.check(counter).unwrap();
+                panic!(
+                    "{} disagrees with rust_for_loop on case {i} of seed {seed}: \
+                     expected {expected}, got {actual}, for {case}",
+                    counter.name()
+                );
+            }
+        }
+    }
+}
+
+/// The seed that `cargo test` uses, so that a failure in CI can be reproduced anywhere.
+const DEFAULT_SEED: u64 = 2023;
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{name} should be a number, not {value:?}")),
+        Err(_) => default,
+    }
+}
+
+#[test]
+fn test_all_implementations_agree() {
+    let seed = env_or("FAST_SP_TEST_SEED", DEFAULT_SEED);
+    let n_cases = env_or("FAST_SP_TEST_CASES", if cfg!(miri) { 10 } else { 1000 });
+
+    assert_all_agree(super::testable(), seed, n_cases);
+}
+
+mod shrinking {
+    use super::*;
+
+    use crate::implementations::{Kernel, Language};
+
+    /// Gets the wrong answer when an 's' directly follows a byte with the high bit
+    /// set, and only when the input doesn't start on a 64-byte boundary.
+    struct Buggy;
+
+    impl Counter for Buggy {
+        fn name(&self) -> &'static str {
+            "buggy"
+        }
+
+        fn language(&self) -> Language {
+            Language::Rust
+        }
+
+        fn kernel(&self) -> Kernel {
+            Kernel::Sized(buggy)
+        }
+    }
+
+    fn buggy(bytes: &[u8]) -> i64 {
+        let misaligned = (bytes.as_ptr() as usize) & 63 != 0;
+        let bug = bytes.windows(2).any(|w| w[0] >= 0x80 && w[1] == b's');
+        rust_for_loop(bytes) + (misaligned && bug) as i64
+    }
+
+    #[test]
+    fn test_shrinks_to_minimal_reproducer() {
+        let mut bytes = random_bytes(&mut Rng::new(1), 1000, b"sp.");
+        bytes[600] = 0xf3;
+        bytes[601] = b's';
+        let case = Case { bytes, offset: 17 };
+
+        let shrunk = case.shrink(&Buggy);
+        assert_eq!(
+            Case {
+                bytes: b"\xf3s".to_vec(),
+                offset: 1
+            },
+            shrunk
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "buggy disagrees with rust_for_loop")]
+    fn test_finds_bug() {
+        let buggy: &dyn Counter = &Buggy;
+        assert_all_agree(std::iter::once(buggy), 0, 1000);
+    }
+}